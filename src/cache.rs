@@ -0,0 +1,160 @@
+use crate::{
+    config::YekConfig,
+    defaults::{DEFAULT_BINARY_SCAN_BYTES, DEFAULT_NULL_BYTE_TOLERANCE},
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::UNIX_EPOCH,
+};
+use tracing::debug;
+
+/// Name of the cache file written to the output directory in incremental mode
+pub const CACHE_FILE_NAME: &str = ".yek-cache.json";
+
+/// Bump whenever the layout of `CacheFile` or `CacheEntry`, or the way file content is read,
+/// changes so older caches are ignored
+const CACHE_VERSION: u32 = 3;
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    /// Hash of the options the cached content was read with (see `read_settings_hash`)
+    settings: String,
+    files: HashMap<String, CacheEntry>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Modification time in nanoseconds since the Unix epoch
+    modified: u64,
+    size: u64,
+    /// Text content, or `None` for files that were skipped as binary
+    content: Option<String>,
+}
+
+/// Per-file content cache used by incremental mode. Files are keyed by path and
+/// considered unchanged while their size and modification time match the cached entry.
+pub struct FileCache {
+    path: PathBuf,
+    settings: String,
+    previous: HashMap<String, CacheEntry>,
+    current: Mutex<HashMap<String, CacheEntry>>,
+    reads: AtomicUsize,
+}
+
+impl FileCache {
+    /// Load the cache from `dir` for a run with `config`. A missing, unreadable or outdated
+    /// cache, or one written with other options for reading files, yields an empty one.
+    pub fn load(dir: &Path, config: &YekConfig) -> Self {
+        let path = dir.join(CACHE_FILE_NAME);
+        let settings = read_settings_hash(config);
+        let previous = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<CacheFile>(&s).ok())
+            .filter(|cache| {
+                let current = cache.version == CACHE_VERSION;
+                if !current {
+                    debug!("Ignoring cache with version {}", cache.version);
+                }
+                current
+            })
+            .filter(|cache| {
+                let same = cache.settings == settings;
+                if !same {
+                    debug!("Ignoring cache read with other options");
+                }
+                same
+            })
+            .map(|cache| cache.files)
+            .unwrap_or_default();
+
+        FileCache {
+            path,
+            settings,
+            previous,
+            current: Mutex::new(HashMap::new()),
+            reads: AtomicUsize::new(0),
+        }
+    }
+
+    /// Return the cached content for `path` if the file is unchanged, otherwise call `read`
+    /// and cache its result. A file `read` fails on is not cached, so the next run reads it
+    /// (and reports the failure) again.
+    pub fn get_or_read(
+        &self,
+        path: &Path,
+        read: impl FnOnce() -> io::Result<Option<String>>,
+    ) -> Option<String> {
+        let Some((modified, size)) = file_stamp(path) else {
+            return read().ok().flatten();
+        };
+        let key = path.to_string_lossy().to_string();
+
+        let entry = match self.previous.get(&key) {
+            Some(entry) if entry.modified == modified && entry.size == size => entry.clone(),
+            _ => {
+                self.reads.fetch_add(1, Ordering::Relaxed);
+                CacheEntry {
+                    modified,
+                    size,
+                    content: read().ok()?,
+                }
+            }
+        };
+
+        let content = entry.content.clone();
+        self.current.lock().unwrap().insert(key, entry);
+        content
+    }
+
+    /// Number of files read from disk (cache misses) since the cache was loaded
+    pub fn reads(&self) -> usize {
+        self.reads.load(Ordering::Relaxed)
+    }
+
+    /// Write the entries seen since loading back to disk. Files that were not
+    /// seen (deleted or no longer included) are dropped from the cache.
+    pub fn save(&self) -> Result<()> {
+        let files = self.current.lock().unwrap().clone();
+        let cache = CacheFile {
+            version: CACHE_VERSION,
+            settings: self.settings.clone(),
+            files,
+        };
+        fs::write(&self.path, serde_json::to_string(&cache)?)?;
+        Ok(())
+    }
+}
+
+/// Hash of the options that decide what a file's cached content is: its encoding and
+/// whether it counts as binary
+fn read_settings_hash(config: &YekConfig) -> String {
+    let settings = serde_json::json!({
+        "encoding": config.encoding,
+        "force_text_extensions": config.force_text_extensions,
+        "binary_scan_bytes": config.binary_scan_bytes.unwrap_or(DEFAULT_BINARY_SCAN_BYTES),
+        "null_byte_tolerance": config.null_byte_tolerance.unwrap_or(DEFAULT_NULL_BYTE_TOLERANCE),
+    });
+    format!("{:x}", Sha256::digest(settings.to_string()))
+}
+
+/// Modification time (nanoseconds since the Unix epoch) and size of a file
+pub fn file_stamp(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos();
+    Some((u64::try_from(modified).ok()?, metadata.len()))
+}
@@ -1,13 +1,28 @@
 use anyhow::{anyhow, Result};
-use bytesize::ByteSize;
 use clap_config_file::ClapConfigFile;
+use path_slash::PathExt;
 use sha2::{Digest, Sha256};
 use std::io::IsTerminal;
-use std::{fs, path::Path, str::FromStr, time::UNIX_EPOCH};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
+    path::Path,
+    time::UNIX_EPOCH,
+};
 
 use crate::{
-    defaults::{BINARY_FILE_EXTENSIONS, DEFAULT_IGNORE_PATTERNS, DEFAULT_OUTPUT_TEMPLATE},
-    priority::PriorityRule,
+    defaults::{
+        BINARY_FILE_EXTENSIONS, DEFAULT_BINARY_SCAN_BYTES, DEFAULT_IGNORE_PATTERNS,
+        DEFAULT_MAX_GIT_DEPTH, DEFAULT_MAX_SCAN_BYTES, DEFAULT_MAX_SCAN_FILES,
+        DEFAULT_NULL_BYTE_TOLERANCE, DEFAULT_OUTPUT_DIR_NAME, DEFAULT_OUTPUT_TEMPLATE,
+        DEFAULT_TOKENIZER, MODEL_PRESETS, OUTPUT_NAME_PLACEHOLDERS, SUPPORTED_COMPRESSIONS,
+        SUPPORTED_LINE_ENDINGS, SUPPORTED_PRIORITY_COMBINE, SUPPORTED_PRIORITY_RULE_SYNTAXES,
+        SUPPORTED_SORT_BY, SUPPORTED_SPLIT_BY, SUPPORTED_TOKENIZERS,
+    },
+    parse_percentage_of, parse_since, parse_size_input, parse_token_limit,
+    priority::{compile_glob, PriorityRule, PriorityTier},
+    redact::Redactor,
+    remote::is_git_url,
 };
 
 #[derive(Clone, Debug, Default, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
@@ -18,63 +33,470 @@ pub enum ConfigFormat {
     Json,
 }
 
-#[derive(ClapConfigFile, Clone)]
+/// How each file is wrapped in the output
+#[derive(
+    Clone, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// `>>>> path` headers, or whatever output_template says
+    #[default]
+    Text,
+    /// `<file path="...">...</file>` elements
+    Xml,
+    /// Fenced code blocks with the language and path as the info string
+    Markdown,
+}
+
+/// Settings are resolved from defaults, then a yek.toml, yek.yaml or yek.json config file
+/// (found by walking up from the current directory, or given with --config-file), then CLI
+/// arguments, which take precedence over both. `--no-config` skips the config file entirely
+/// so only CLI arguments and defaults apply, e.g. for deterministic runs in CI.
+#[derive(ClapConfigFile, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
 #[config_file_name = "yek"]
 #[config_file_formats = "toml,yaml,json"]
 pub struct YekConfig {
-    /// Input directories to process
+    /// Input directories to process. Git URLs (https://, ssh://, git@host:path, ...) are
+    /// shallow-cloned to a temp dir, serialized, and removed again afterwards.
     #[config_arg(positional)]
     pub input_dirs: Vec<String>,
 
+    /// Keep the clones of remote repositories instead of removing them after serializing
+    #[config_arg(long = "keep-clone")]
+    pub keep_clone: bool,
+
+    /// Read newline-separated file paths from stdin instead of walking input directories.
+    /// Passing `-` as an input directory does the same.
+    #[config_arg(long = "from-stdin")]
+    pub from_stdin: bool,
+
+    /// Like from_stdin, but the paths on stdin are separated by NUL bytes, as printed by
+    /// `git ls-files -z` or `find -print0`, so they may contain spaces and newlines
+    #[config_arg(long = "stdin-filenames0")]
+    pub stdin_filenames0: bool,
+
     /// Print version of yek
     #[config_arg(long = "version", short = 'V')]
     pub version: bool,
 
-    /// Max size per chunk. e.g. "10MB" or "128K" or when using token counting mode, "100" or "128K"
+    /// Max size per chunk. e.g. "10MB" or "128K" or when using token counting mode, "100" or "128K".
+    /// With a model, a percentage of its context window such as "80%" can be given instead.
     #[config_arg(default_value = "10MB")]
     pub max_size: String,
 
+    /// Max size of the whole output, across all chunks with split_by. Same units as
+    /// max_size, or tokens in token mode. The lowest-priority files that don't fit are dropped.
+    #[config_arg(long = "max-total-size")]
+    pub max_total_size: Option<String>,
+
+    /// Skip individual files larger than this, e.g. "1MB". Same units as max_size
+    #[config_arg(long = "max-file-size")]
+    pub max_file_size: Option<String>,
+
+    /// Keep only the N highest-priority files. Applied before the max_size/tokens cap.
+    #[config_arg()]
+    pub max_files: Option<usize>,
+
+    /// Leave out files whose final priority (rule scores plus Git boost) is below this.
+    /// With 1, files that match no priority rule and get no boost are dropped.
+    #[config_arg(long = "min-priority")]
+    pub min_priority: Option<i32>,
+
     /// Use token mode instead of byte mode
     #[config_arg()]
     pub tokens: String,
 
+    /// Tokenizer used in token mode: cl100k_base, o200k_base, p50k_base, r50k_base or whitespace
+    #[config_arg(default_value = "cl100k_base")]
+    pub tokenizer: String,
+
+    /// Model the output is meant for, e.g. "gpt-4o". Turns on token mode with the model's
+    /// tokenizer and context window as the limit, unless tokens or tokenizer are set.
+    #[config_arg()]
+    pub model: Option<String>,
+
+    /// Text encoding of input files: "auto" (byte-order mark, then UTF-8, then Windows-1252),
+    /// or a label such as "utf-8", "latin1" or "utf-16". Files that don't decode are skipped
+    /// and counted as unreadable. When unset, invalid UTF-8 bytes are replaced with U+FFFD.
+    #[config_arg()]
+    pub encoding: Option<String>,
+
+    /// Only include files last committed at or after this time: a Unix timestamp,
+    /// YYYY-MM-DD or an RFC 3339 date-time. Only the last max_git_depth commits are considered.
+    #[config_arg()]
+    pub since: Option<String>,
+
+    /// With --since, also drop files that Git doesn't track
+    #[config_arg(long = "exclude-untracked")]
+    pub exclude_untracked: bool,
+
+    /// Only include files that differ between this Git revision (e.g. "HEAD" or
+    /// "origin/main") and the working tree, for reviewing a change
+    #[config_arg()]
+    pub diff: Option<String>,
+
+    /// With --diff, also include files that Git doesn't track
+    #[config_arg(long = "include-untracked")]
+    pub include_untracked: bool,
+
+    /// Boost recently modified files by their file system modification time when there
+    /// is no Git history to take commit times from
+    #[config_arg(long = "use-mtime")]
+    pub use_mtime: bool,
+
     /// Enable JSON output
     #[config_arg()]
     pub json: bool,
 
+    /// Output JSON Lines: one {"filename", "priority", "content"} object per line instead of
+    /// one JSON array. When streaming, each line is written out as soon as it's rendered.
+    /// The table of contents and header/footer templates are not used.
+    #[config_arg()]
+    pub jsonl: bool,
+
     /// Enable debug output
     #[config_arg()]
     pub debug: bool,
 
+    /// Don't show the progress bar that is otherwise drawn on stderr while files are read,
+    /// nor the summary line printed after writing an output file
+    #[config_arg()]
+    pub quiet: bool,
+
+    /// List the files that would be included, with their size and priority, without writing output
+    #[config_arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Print each file's token and byte count, largest first, and the totals instead of the
+    /// output. Uses the configured tokenizer and ignores the max_size/tokens cap.
+    #[config_arg(long = "count-only")]
+    pub count_only: bool,
+
+    /// For each file that would be included, show the priority rule that scored it highest,
+    /// that rule's score, the Git boost and the final priority, without writing output
+    #[config_arg()]
+    pub explain: bool,
+
+    /// Output only the sorted paths of the files a full run would include, without their
+    /// contents. Ignore, include, priority and size cap settings still apply.
+    #[config_arg(long = "paths-only")]
+    pub paths_only: bool,
+
+    /// Like paths_only, but as an indented directory tree
+    #[config_arg()]
+    pub tree: bool,
+
+    /// Report the files skipped as binary on stderr, grouped by whether their extension
+    /// or only their content marked them as binary. Bypasses the incremental cache.
+    #[config_arg(long = "list-binary")]
+    pub list_binary: bool,
+
+    /// Copy the output to the system clipboard instead of writing a file or printing it.
+    /// Falls back to printing to stdout if the clipboard is unavailable.
+    #[config_arg()]
+    pub clipboard: bool,
+
+    /// Compress the output. Only "gzip" is supported; output files get a ".gz" suffix
+    #[config_arg()]
+    pub compress: Option<String>,
+
+    /// Write one output file per top-level directory instead of a single file.
+    /// Only "directory" is supported. Has no effect when streaming to stdout.
+    #[config_arg(long = "split-by")]
+    pub split_by: Option<String>,
+
+    /// Put every file into one output, ignoring max_size and tokens, so it can be pasted
+    /// into a single prompt. Can't be combined with split_by.
+    #[config_arg(long = "single-document")]
+    pub single_document: bool,
+
+    /// Write a ".meta.json" file next to each output file, listing its files with their
+    /// priority and the byte offset where each file's entry starts. Not used when streaming.
+    #[config_arg(long = "sidecar")]
+    pub sidecar: bool,
+
+    /// Order of files in the output: priority (most important last), path or size (ascending).
+    /// Which files are kept under max_files and the size cap is still decided by priority.
+    #[config_arg(long = "sort-by", default_value = "priority")]
+    pub sort_by: String,
+
+    /// Name of the output file, e.g. "{repo}-{date}.{ext}". Supports {checksum}, {repo},
+    /// {date} (YYYY-MM-DD, UTC), {ext} and, required with split_by, {group}.
+    /// Defaults to "yek-output-{checksum}.{ext}". A ".gz" suffix is added when compressing.
+    #[config_arg(long = "output-name-template")]
+    pub output_name_template: Option<String>,
+
+    /// Also write yek-manifest.json to the output directory, listing the output files, the
+    /// checksum, total bytes and tokens, and each included file with its priority and the
+    /// index of the output file it's in. Has no effect when streaming to stdout.
+    #[config_arg()]
+    pub manifest: bool,
+
     /// Output directory. If none is provided & stdout is a TTY, we pick a temp dir
     #[config_arg()]
     pub output_dir: Option<String>,
 
+    /// Name of the directory created in the system temp dir when no output_dir is set.
+    /// Directories with this name are never serialized, and neither is output_dir.
+    #[config_arg(long = "output-dir-name", default_value = "yek-output")]
+    pub output_dir_name: String,
+
+    /// Cache file contents in output_dir/.yek-cache.json and only re-read files whose
+    /// size or modification time changed since the last run. Applies to walked directories,
+    /// not to file lists read from stdin.
+    #[config_arg()]
+    pub incremental: bool,
+
+    /// When the output file named after the current checksum already exists, print its path
+    /// and skip serializing. The checksum covers the path, size and modification time of
+    /// every walked file, the HEAD commit of each input's repository and all settings.
+    /// Remote repositories and stdin file lists are always serialized. Not used when
+    /// streaming, and can't be combined with split_by.
+    #[config_arg(long = "skip-if-unchanged")]
+    pub skip_if_unchanged: bool,
+
+    /// Keep running after the first run and serialize again whenever a file in the input
+    /// directories changes. Changes to ignored files and to the output directory are skipped.
+    #[config_arg()]
+    pub watch: bool,
+
+    /// How each file is wrapped: text (uses output_template), xml or markdown. Not used with --json
+    #[config_arg(long = "output-format", default_value = "text")]
+    pub output_format: OutputFormat,
+
+    /// Add the language inferred from the file name, or the `#!` line of scripts without an
+    /// extension, to text headers, e.g. ">>>> src/main.rs (rust)"
+    #[config_arg(long = "show-language")]
+    pub show_language: bool,
+
+    /// Add each file's size and token count to text headers, e.g. ">>>> src/main.rs (1.2 KB, 340 tokens)"
+    #[config_arg(long = "show-file-stats")]
+    pub show_file_stats: bool,
+
+    /// Text put in front of every path in the output, e.g. "myrepo/" to get headers like
+    /// ">>>> myrepo/src/main.rs". Ignore and priority rules still match the plain path.
+    #[config_arg(long = "path-prefix")]
+    pub path_prefix: Option<String>,
+
+    /// Show absolute paths in the output, joined onto the canonicalized input directory,
+    /// e.g. ">>>> /home/me/repo/src/main.rs". Ignore and priority rules still match the
+    /// relative path.
+    #[config_arg(long = "absolute-paths")]
+    pub absolute_paths: bool,
+
+    /// Add the short hash of the last commit that touched each file to its header, e.g.
+    /// ">>>> src/main.rs @a1b2c3d". Only commits within max_git_depth are looked at, and
+    /// files without one get no annotation.
+    #[config_arg(long = "show-commit")]
+    pub show_commit: bool,
+
     /// Output template. Defaults to ">>>> FILE_PATH\nFILE_CONTENT"
     #[config_arg(default_value = ">>>> FILE_PATH\nFILE_CONTENT")]
     pub output_template: String,
 
-    /// Ignore patterns
+    /// Emit the content of byte-identical files once. The highest-priority copy keeps it,
+    /// and the others are listed as ">>>> path (duplicate of other/path)" with no content
+    /// and only count towards the size cap as such.
+    #[config_arg()]
+    pub dedup: bool,
+
+    /// Text placed before the output. Supports {file_count}, {total_tokens} and {repo_name}.
+    /// With split_by it starts the first chunk only, and counts the files and tokens of all
+    /// chunks. Not used with --json
+    #[config_arg(long = "header-template")]
+    pub header_template: Option<String>,
+
+    /// Text placed after the output. Supports the same variables as header_template
+    #[config_arg(long = "footer-template")]
+    pub footer_template: Option<String>,
+
+    /// Start the output with a table of contents listing every included file. With split_by,
+    /// only the first chunk has one, listing the files of all chunks with the chunk (from 1)
+    /// each is in
+    #[config_arg()]
+    pub toc: bool,
+
+    /// Start the output with a directory tree of every included file, above the table of
+    /// contents. With split_by, only the first output file has it.
+    #[config_arg(long = "tree-header")]
+    pub tree_header: bool,
+
+    /// Put a banner such as "=== HIGH PRIORITY ===" before the first file of each
+    /// priority tier (see priority_tiers)
+    #[config_arg(long = "tier-headers")]
+    pub tier_headers: bool,
+
+    /// File of standing instructions emitted verbatim at the very top of the output, ahead
+    /// of the header, table of contents and files. It counts towards the size cap, and with
+    /// split_by only the first output file has it.
+    #[config_arg(long = "prompt-file")]
+    pub prompt_file: Option<String>,
+
+    /// Line put between consecutive files, e.g. a row of dashes. Never before the first
+    /// file or after the last one.
+    #[config_arg(long = "file-separator")]
+    pub file_separator: Option<String>,
+
+    /// Prefix each line of file content with its 1-based line number, e.g. "   12 | code"
+    #[config_arg(long = "line-numbers")]
+    pub line_numbers: bool,
+
+    /// Remove comments from files in languages with known comment syntax (//, /* */ or #).
+    /// Line numbers then count the remaining lines.
+    #[config_arg(long = "strip-comments")]
+    pub strip_comments: bool,
+
+    /// Replace runs of blank lines in file content with a single blank line
+    #[config_arg(long = "collapse-blank-lines")]
+    pub collapse_blank_lines: bool,
+
+    /// Cut lines of file content longer than this many characters, e.g. in minified files,
+    /// and mark each with "…[truncated N chars]"
+    #[config_arg(long = "max-line-length")]
+    pub max_line_length: Option<usize>,
+
+    /// Convert line endings in file content to "lf" or "crlf" before it's counted against
+    /// the size cap and written, or leave them as they are with "none" (the default)
+    #[config_arg(long = "normalize-line-endings")]
+    pub normalize_line_endings: Option<String>,
+
+    /// Replace secrets in file content (private keys, AWS and other API keys, secret-looking
+    /// assignments and long high-entropy strings) with "***REDACTED***" before it's output
+    #[config_arg(long = "redact")]
+    pub redact: bool,
+
+    /// Extra regexes of secrets to mask with redact. When a pattern has a capture group,
+    /// only the group is masked, e.g. "internal_id: (\\w+)"
+    #[config_arg(long = "redact-patterns", multi_value_behavior = "extend")]
+    pub redact_patterns: Vec<String>,
+
+    /// Ignore patterns, using .gitignore glob syntax (e.g. "*.log" or "src/**/*.test.ts")
     #[config_arg(long = "ignore-patterns", multi_value_behavior = "extend")]
     pub ignore_patterns: Vec<String>,
 
+    /// Extra ignore files using .gitignore syntax, e.g. ".dockerignore". Relative paths are
+    /// looked up in each input directory; "!" lines re-include files.
+    #[config_arg(long = "ignore-file", multi_value_behavior = "extend")]
+    pub ignore_files: Vec<String>,
+
+    /// Include patterns. When set, only files matching at least one pattern are kept
+    /// (ignore patterns still apply on top). Same glob syntax as ignore patterns.
+    #[config_arg(long = "include-patterns", multi_value_behavior = "extend")]
+    pub include_patterns: Vec<String>,
+
     /// Unignore patterns. Yek has some built-in ignore patterns, but you can override them here.
     #[config_arg(long = "unignore-patterns", multi_value_behavior = "extend")]
     pub unignore_patterns: Vec<String>,
 
+    /// Don't apply the built-in ignore patterns (VCS, dependency and build directories, lock files)
+    #[config_arg(long = "no-default-ignores")]
+    pub no_default_ignores: bool,
+
+    /// Follow symbolic links to files and directories while walking input directories
+    #[config_arg(long = "follow-symlinks")]
+    pub follow_symlinks: bool,
+
+    /// Include hidden files and directories (names starting with a dot), except `.git`.
+    /// Ignore and include patterns still apply to them.
+    #[config_arg()]
+    pub hidden: bool,
+
+    /// How many directory levels below each input directory to walk: 0 only takes the
+    /// files directly in it. No limit by default.
+    #[config_arg(long = "max-depth")]
+    pub max_depth: Option<usize>,
+
+    /// Fail on the first file that can't be read instead of skipping it with a warning
+    #[config_arg()]
+    pub strict: bool,
+
+    /// Treat config warnings (options that have no effect, sizes without a unit, ...) as
+    /// errors, and fail on a config file with a syntax error, an option of the wrong type or
+    /// an unknown option, so a mistyped config fails the run instead of being half applied
+    #[config_arg(long = "strict-config")]
+    pub strict_config: bool,
+
+    /// Abort when walking an input directory finds more files than this, e.g. after
+    /// pointing yek at `/` by mistake. 0 turns the check off.
+    #[config_arg(long = "max-scan-files", default_value = "100000")]
+    pub max_scan_files: usize,
+
+    /// Abort when the files found walking an input directory add up to more than this,
+    /// e.g. "2GB". 0 turns the check off.
+    #[config_arg(long = "max-scan-bytes", default_value = "2GB")]
+    pub max_scan_bytes: String,
+
     /// Priority rules
     #[config_arg(accept_from = "config_only")]
     pub priority_rules: Vec<PriorityRule>,
 
-    /// Binary file extensions to ignore
+    /// How priority rule patterns are written when a rule doesn't set its own syntax:
+    /// "regex" (the default) or "glob" (e.g. "src/**/*.rs")
+    #[config_arg(accept_from = "config_only")]
+    pub priority_rule_syntax: String,
+
+    /// Priority bands labelled with tier_headers, e.g. [{name: "CORE", min_priority: 100}].
+    /// A file is in the highest tier its priority reaches. Defaults to high (100),
+    /// medium (50) and low priority.
+    #[config_arg(accept_from = "config_only")]
+    pub priority_tiers: Vec<PriorityTier>,
+
+    /// Priority of files that match no priority rule, by extension, e.g. {rs: 100, md: 10}.
+    /// Extensions are matched case-insensitively and may be written with a leading dot.
+    #[config_arg(accept_from = "config_only")]
+    pub extension_priorities: HashMap<String, i32>,
+
+    /// How the scores of several matching priority rules combine: "sum" (the default) or
+    /// "max". The Git boost is added on top either way.
+    #[config_arg(accept_from = "config_only")]
+    pub priority_combine: String,
+
+    /// Score added per directory level a file is nested in, negated so that a positive value
+    /// favors shallow files: with 10, "README.md" gets 0 and "a/b/c.rs" gets -20.
+    /// Added on top of the rules, like the Git boost.
+    #[config_arg(accept_from = "config_only", default_value = "0")]
+    pub depth_priority: i32,
+
+    /// Binary file extensions to ignore, on top of the built-in ones
     #[config_arg(accept_from = "config_only", default_value = BINARY_FILE_EXTENSIONS)]
     pub binary_extensions: Vec<String>,
 
+    /// Use binary_extensions instead of the built-in list rather than adding to it
+    #[config_arg(accept_from = "config_only")]
+    pub binary_extensions_replace: bool,
+
+    /// Print the binary extensions in effect, the built-in ones merged with
+    /// binary_extensions, one per line, and exit
+    #[config_arg(long = "print-binary-extensions")]
+    pub print_binary_extensions: bool,
+
+    /// Extensions of files that are always read as text, skipping binary detection, e.g.
+    /// "svg" or "min.js" for files that may contain null bytes
+    #[config_arg(accept_from = "config_only")]
+    pub force_text_extensions: Vec<String>,
+
+    /// How many leading bytes of each file are scanned for binary detection (default 8192)
+    #[config_arg(accept_from = "config_only")]
+    pub binary_scan_bytes: Option<usize>,
+
+    /// Share of null bytes (0.0..1.0) in the scanned bytes above which a file is binary
+    /// (default 0.1)
+    #[config_arg(accept_from = "config_only")]
+    pub null_byte_tolerance: Option<f64>,
+
     /// Maximum additional boost from Git commit times (0..1000)
     #[config_arg(accept_from = "config_only")]
     pub git_boost_max: Option<i32>,
 
-    /// True if we should stream output to stdout (computed)
+    /// Share of the Git boost (0.0..1.0) that comes from how many commits touched a file
+    /// rather than how recently it changed, so frequently changed files rank higher
+    #[config_arg(accept_from = "config_only", default_value = "0.0")]
+    pub git_frequency_weight: f64,
+
+    /// Stream the output to stdout, even when it's a terminal. Without it, output streams
+    /// when stdout isn't a terminal.
+    #[config_arg()]
     pub stream: bool,
 
     /// True if we should count tokens, not bytes (computed)
@@ -83,9 +505,14 @@ pub struct YekConfig {
     /// Final resolved output file path (only used if not streaming)
     pub output_file_full_path: Option<String>,
 
-    /// Maximum depth to search for Git commit times
-    #[config_arg(accept_from = "config_only", default_value = "100")]
-    pub max_git_depth: i32,
+    /// Maximum depth to search for Git commit times (default 100)
+    #[config_arg(accept_from = "config_only")]
+    pub max_git_depth: Option<i32>,
+
+    /// Honor .gitignore, .ignore and global gitignore files during the walk, and leave out
+    /// paths marked export-ignore in a Git repository's .gitattributes (default true)
+    #[config_arg(accept_from = "config_only")]
+    pub respect_gitignore: Option<bool>,
 }
 
 /// Provide defaults so tests or other callers can create a baseline YekConfig easily.
@@ -93,27 +520,105 @@ impl Default for YekConfig {
     fn default() -> Self {
         Self {
             input_dirs: Vec::new(),
+            keep_clone: false,
+            from_stdin: false,
+            stdin_filenames0: false,
             version: false,
             max_size: "10MB".to_string(),
+            max_total_size: None,
+            max_file_size: None,
+            max_files: None,
+            min_priority: None,
             tokens: String::new(),
+            tokenizer: DEFAULT_TOKENIZER.to_string(),
+            model: None,
+            encoding: None,
+            since: None,
+            exclude_untracked: false,
+            diff: None,
+            include_untracked: false,
+            use_mtime: false,
             json: false,
+            jsonl: false,
             debug: false,
+            quiet: false,
+            dry_run: false,
+            count_only: false,
+            explain: false,
+            paths_only: false,
+            tree: false,
+            list_binary: false,
+            clipboard: false,
+            compress: None,
+            split_by: None,
+            single_document: false,
+            sidecar: false,
+            output_name_template: None,
+            manifest: false,
+            sort_by: "priority".to_string(),
             output_dir: None,
+            output_dir_name: DEFAULT_OUTPUT_DIR_NAME.to_string(),
+            incremental: false,
+            skip_if_unchanged: false,
+            watch: false,
+            output_format: OutputFormat::Text,
+            show_language: false,
+            show_file_stats: false,
+            path_prefix: None,
+            absolute_paths: false,
+            show_commit: false,
             output_template: DEFAULT_OUTPUT_TEMPLATE.to_string(),
+            dedup: false,
+            header_template: None,
+            footer_template: None,
+            toc: false,
+            tree_header: false,
+            tier_headers: false,
+            prompt_file: None,
+            file_separator: None,
+            line_numbers: false,
+            strip_comments: false,
+            collapse_blank_lines: false,
+            max_line_length: None,
+            redact: false,
+            redact_patterns: Vec::new(),
+            normalize_line_endings: None,
             ignore_patterns: Vec::new(),
+            ignore_files: Vec::new(),
+            include_patterns: Vec::new(),
             unignore_patterns: Vec::new(),
+            no_default_ignores: false,
+            follow_symlinks: false,
+            hidden: false,
+            max_depth: None,
+            strict: false,
+            strict_config: false,
+            max_scan_files: DEFAULT_MAX_SCAN_FILES,
+            max_scan_bytes: DEFAULT_MAX_SCAN_BYTES.to_string(),
             priority_rules: Vec::new(),
+            priority_rule_syntax: "regex".to_string(),
+            priority_tiers: Vec::new(),
+            extension_priorities: HashMap::new(),
+            priority_combine: "sum".to_string(),
+            depth_priority: 0,
             binary_extensions: BINARY_FILE_EXTENSIONS
                 .iter()
                 .map(|s| s.to_string())
                 .collect(),
+            binary_extensions_replace: false,
+            print_binary_extensions: false,
+            force_text_extensions: Vec::new(),
+            binary_scan_bytes: Some(DEFAULT_BINARY_SCAN_BYTES),
+            null_byte_tolerance: Some(DEFAULT_NULL_BYTE_TOLERANCE),
             git_boost_max: Some(100),
+            git_frequency_weight: 0.0,
 
             // computed fields
             stream: false,
             token_mode: false,
             output_file_full_path: None,
-            max_git_depth: 100,
+            max_git_depth: Some(DEFAULT_MAX_GIT_DEPTH),
+            respect_gitignore: Some(true),
         }
     }
 }
@@ -129,18 +634,51 @@ impl YekConfig {
 }
 
 impl YekConfig {
+    /// Ignore patterns that keep earlier output out of the input: any directory named
+    /// output_dir_name, and output_dir itself when it's inside an input directory.
+    pub fn output_ignore_patterns(&self) -> Vec<String> {
+        let mut patterns = vec![format!("{}/", self.output_dir_name)];
+        let Some(output_dir) = self.output_dir.as_deref() else {
+            return patterns;
+        };
+        let Ok(output_dir) = Path::new(output_dir).canonicalize() else {
+            return patterns;
+        };
+        for dir in &self.input_dirs {
+            let Ok(input_dir) = Path::new(dir).canonicalize() else {
+                continue;
+            };
+            if let Ok(rel) = output_dir.strip_prefix(&input_dir) {
+                if let Some(rel) = rel.to_slash().filter(|rel| !rel.is_empty()) {
+                    patterns.push(format!("/{}/", rel));
+                }
+            }
+        }
+        patterns
+    }
+
+    /// The output directory: output_dir, or output_dir_name in the system temp dir
+    pub fn resolved_output_dir(&self) -> String {
+        match &self.output_dir {
+            Some(dir) => dir.clone(),
+            None => std::env::temp_dir()
+                .join(&self.output_dir_name)
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
+
     /// Ensure output directory exists and is valid. Returns the resolved output directory path.
     pub fn ensure_output_dir(&self) -> Result<String> {
         if self.stream {
             return Ok(String::new());
         }
+        self.create_output_dir()
+    }
 
-        let output_dir = if let Some(dir) = &self.output_dir {
-            dir.clone()
-        } else {
-            let temp_dir = std::env::temp_dir().join("yek-output");
-            temp_dir.to_string_lossy().to_string()
-        };
+    /// Same as `ensure_output_dir`, also when streaming, e.g. for the incremental cache
+    pub fn create_output_dir(&self) -> Result<String> {
+        let output_dir = self.resolved_output_dir();
 
         let path = Path::new(&output_dir);
         if path.exists() && !path.is_dir() {
@@ -156,10 +694,34 @@ impl YekConfig {
         Ok(output_dir)
     }
 
+    /// Read the config file at `path` strictly: a syntax error, an option of the wrong
+    /// type or an unknown option is an error, where the CLI would fall back to defaults
+    pub fn check_config_file(path: &Path) -> Result<()> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("strict_config: cannot read {}: {}", path.display(), e))?;
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let parsed = match extension.as_str() {
+            "toml" => toml::from_str::<YekConfig>(&text).map_err(|e| e.to_string()),
+            "json" => serde_json::from_str::<YekConfig>(&text).map_err(|e| e.to_string()),
+            _ => serde_yaml::from_str::<YekConfig>(&text).map_err(|e| e.to_string()),
+        };
+        parsed.map(|_| ()).map_err(|e| {
+            anyhow!(
+                "strict_config: invalid config file {}: {}",
+                path.display(),
+                e
+            )
+        })
+    }
+
     /// Parse from CLI + config file, fill in computed fields, and validate.
     pub fn init_config() -> Self {
         // 1) parse from CLI and optional config file:
-        let mut cfg = YekConfig::parse();
+        let (cfg, config_file, _) = YekConfig::parse_info();
 
         // Handle version flag
         if cfg.version {
@@ -167,34 +729,124 @@ impl YekConfig {
             std::process::exit(0);
         }
 
-        // 2) compute derived fields:
+        // 2) resolve it with the environment, then report warnings
+        let env = std::env::vars()
+            .filter(|(name, _)| name.starts_with("YEK_"))
+            .collect::<HashMap<_, _>>();
+        let args = std::env::args().collect::<Vec<_>>();
+        let is_terminal = std::io::stdout().is_terminal() || std::env::var("FORCE_TTY").is_ok();
+        let mut cfg =
+            match YekConfig::from_sources(cfg, config_file.as_deref(), &env, &args, is_terminal) {
+                Ok(cfg) => cfg,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+        // 3) create the output directory, or stream to stdout when that fails
+        if cfg.output_dir.is_some() {
+            if let Err(e) = cfg.create_output_dir() {
+                eprintln!("Warning: Failed to create output directory: {}", e);
+                cfg.stream = true;
+                cfg.output_dir = None;
+            }
+        }
+        for warning in cfg.warnings() {
+            eprintln!("Warning: {}", warning);
+        }
+
+        if cfg.print_binary_extensions {
+            println!("{}", cfg.binary_extensions.join("\n"));
+            std::process::exit(0);
+        }
+
+        cfg
+    }
+
+    /// Resolve a parsed config into the one a run uses. `parsed` holds the CLI arguments
+    /// merged over the config file and the defaults, as `YekConfig::parse()` returns it.
+    /// The YEK_* variables in `env` then apply to options not given as flags in `args`
+    /// (see `apply_env_overrides`), followed by the model preset, the built-in ignore
+    /// patterns and binary extensions, and validation. As for the CLI, output streams when
+    /// `is_terminal` says stdout isn't a terminal, and output_dir is resolved when it is
+    /// needed (see `resolved_output_dir`), but not created. `config_file` is the file
+    /// `parsed` was read from, if any; with strict_config it has to parse cleanly (see
+    /// `check_config_file`). Warnings are left to the caller (see `warnings`), unless
+    /// strict_config makes them errors.
+    pub fn from_sources(
+        parsed: YekConfig,
+        config_file: Option<&Path>,
+        env: &HashMap<String, String>,
+        args: &[String],
+        is_terminal: bool,
+    ) -> Result<YekConfig> {
+        let mut cfg = parsed;
+
+        // The CLI falls back to defaults on a broken config file, strict_config doesn't
+        if let (true, Some(path)) = (cfg.strict_config, config_file) {
+            YekConfig::check_config_file(path)?;
+        }
+
+        // Compute derived fields:
         cfg.token_mode = !cfg.tokens.is_empty();
-        let force_tty = std::env::var("FORCE_TTY").is_ok();
 
-        cfg.stream = !std::io::stdout().is_terminal() && !force_tty;
+        // A dry run, count only or explanation prints a summary, so it never needs an
+        // output directory
+        cfg.stream = cfg.stream || !is_terminal || cfg.prints_summary();
+
+        // Environment variables override the config file, but not flags on the command line
+        cfg.apply_env_overrides(env, args)?;
+        cfg.apply_model_preset()?;
+
+        // "-" as an input means "read the file list from stdin", relative to the current dir
+        if cfg.input_dirs.iter().any(|dir| dir == "-") {
+            cfg.from_stdin = true;
+            cfg.input_dirs.retain(|dir| dir != "-");
+        }
+        if cfg.stdin_filenames0 {
+            cfg.from_stdin = true;
+        }
 
         // default input dirs to current dir if none:
         if cfg.input_dirs.is_empty() {
             cfg.input_dirs.push(".".to_string());
         }
 
-        // Extend binary extensions with the built-in list:
-        let mut merged_bins = BINARY_FILE_EXTENSIONS
-            .iter()
-            .map(|s| s.to_string())
-            .collect::<Vec<_>>();
+        // Extend binary extensions with the built-in list, unless they replace it, sorted
+        // and without duplicates:
+        let mut merged_bins = if cfg.binary_extensions_replace {
+            Vec::new()
+        } else {
+            BINARY_FILE_EXTENSIONS
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        };
         merged_bins.append(&mut cfg.binary_extensions);
         cfg.binary_extensions = merged_bins
             .into_iter()
-            .collect::<std::collections::HashSet<_>>()
+            .collect::<BTreeSet<_>>()
             .into_iter()
             .collect();
 
-        // Always start with default ignore patterns, then add user's:
-        let mut ignore = DEFAULT_IGNORE_PATTERNS
-            .iter()
-            .map(|s| s.to_string())
-            .collect::<Vec<_>>();
+        // Resolve the output directory, created by the caller. Incremental mode keeps its
+        // cache there, even when streaming.
+        if !cfg.stream || cfg.incremental {
+            cfg.output_dir = Some(cfg.resolved_output_dir());
+        }
+
+        // Start with default ignore patterns unless disabled, then add user's:
+        let mut ignore = if cfg.no_default_ignores {
+            Vec::new()
+        } else {
+            DEFAULT_IGNORE_PATTERNS
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        };
+        // Never serialize our own output
+        ignore.extend(cfg.output_ignore_patterns());
         ignore.extend(cfg.ignore_patterns);
         cfg.ignore_patterns = ignore;
 
@@ -202,33 +854,142 @@ impl YekConfig {
         cfg.ignore_patterns
             .extend(cfg.unignore_patterns.iter().map(|pat| format!("!{}", pat)));
 
-        // Handle output directory setup
-        if !cfg.stream {
-            match cfg.ensure_output_dir() {
-                Ok(dir) => cfg.output_dir = Some(dir),
-                Err(e) => {
-                    eprintln!("Warning: Failed to create output directory: {}", e);
-                    cfg.stream = true; // Fall back to streaming mode
-                }
-            }
+        // A config file without priority_combine or priority_rule_syntax leaves them empty
+        if cfg.priority_combine.is_empty() {
+            cfg.priority_combine = "sum".to_string();
+        }
+        if cfg.priority_rule_syntax.is_empty() {
+            cfg.priority_rule_syntax = "regex".to_string();
         }
 
         // By default, we start with no final output_file_full_path:
         cfg.output_file_full_path = None;
 
-        // 3) Validate
-        if let Err(e) = cfg.validate() {
-            eprintln!("Error: {}", e);
-            std::process::exit(1);
+        cfg.validate()?;
+        if cfg.strict_config {
+            let warnings = cfg.warnings();
+            if !warnings.is_empty() {
+                return Err(anyhow!(
+                    "strict_config: the config has {} problem(s):\n  {}",
+                    warnings.len(),
+                    warnings.join("\n  ")
+                ));
+            }
         }
+        Ok(cfg)
+    }
 
-        cfg
+    /// Apply the YEK_* variables in `env` on top of the options read from the config file.
+    /// An option also given as a flag in `args` (the command line) keeps the flag's value.
+    ///
+    /// - YEK_MAX_SIZE, YEK_TOKENS and YEK_OUTPUT_DIR set max_size, tokens and output_dir
+    /// - YEK_STREAM ("true"/"false", "1"/"0", "yes"/"no" or "on"/"off") turns streaming to
+    ///   stdout on or off instead of deciding by whether stdout is a terminal
+    pub fn apply_env_overrides(
+        &mut self,
+        env: &HashMap<String, String>,
+        args: &[String],
+    ) -> Result<()> {
+        let on_command_line = |flag: &str| {
+            args.iter()
+                .any(|arg| arg == flag || arg.starts_with(&format!("{}=", flag)))
+        };
+
+        if let Some(max_size) = env.get("YEK_MAX_SIZE") {
+            if !on_command_line("--max-size") {
+                parse_size_input(max_size, false)
+                    .map_err(|_| anyhow!("YEK_MAX_SIZE: Invalid size format: '{}'", max_size))?;
+                self.max_size = max_size.clone();
+            }
+        }
+        if let Some(tokens) = env.get("YEK_TOKENS") {
+            if !on_command_line("--tokens") {
+                parse_token_limit(tokens)
+                    .map_err(|_| anyhow!("YEK_TOKENS: Invalid token size: '{}'", tokens))?;
+                self.tokens = tokens.clone();
+                self.token_mode = !self.tokens.is_empty();
+            }
+        }
+        if let Some(output_dir) = env.get("YEK_OUTPUT_DIR") {
+            if !on_command_line("--output-dir") {
+                self.output_dir = Some(output_dir.clone());
+            }
+        }
+        if let Some(stream) = env.get("YEK_STREAM") {
+            let stream = match stream.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => true,
+                "0" | "false" | "no" | "off" => false,
+                _ => {
+                    return Err(anyhow!(
+                        "YEK_STREAM: expected true or false, got '{}'",
+                        stream
+                    ))
+                }
+            };
+            if !on_command_line("--stream") {
+                self.stream = stream || self.prints_summary();
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill in token mode from the `model` preset (see `MODEL_PRESETS`): the model's context
+    /// window as the token limit and its tokenizer. A token limit or a tokenizer other than
+    /// the default that is already set is kept.
+    pub fn apply_model_preset(&mut self) -> Result<()> {
+        let Some(model) = &self.model else {
+            return Ok(());
+        };
+        let Some((_, tokenizer, context_window)) = MODEL_PRESETS
+            .iter()
+            .find(|(name, _, _)| name.eq_ignore_ascii_case(model))
+        else {
+            return Err(anyhow!(
+                "model: Unknown model '{}'. Expected one of: {}",
+                model,
+                MODEL_PRESETS
+                    .iter()
+                    .map(|(name, _, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        };
+
+        // A percentage (e.g. "80%") leaves the rest of the window for the prompt. It may be
+        // given as tokens or, when no token count is set, as max_size.
+        if self.tokens.trim().ends_with('%') {
+            let budget = parse_percentage_of(&self.tokens, *context_window)
+                .map_err(|e| anyhow!("tokens: {}", e))?;
+            self.tokens = budget.to_string();
+        } else if self.tokens.is_empty() && self.max_size.trim().ends_with('%') {
+            let budget = parse_percentage_of(&self.max_size, *context_window)
+                .map_err(|e| anyhow!("max_size: {}", e))?;
+            self.tokens = budget.to_string();
+        } else if self.tokens.is_empty() {
+            self.tokens = context_window.to_string();
+        }
+        if self.tokenizer == DEFAULT_TOKENIZER {
+            self.tokenizer = tokenizer.to_string();
+        }
+        self.token_mode = true;
+        Ok(())
+    }
+
+    /// Whether a summary is printed instead of the output (dry_run, count_only, explain or
+    /// print_binary_extensions)
+    fn prints_summary(&self) -> bool {
+        self.dry_run || self.count_only || self.explain || self.print_binary_extensions
     }
 
     /// Compute a quick checksum for the *top-level listing* of each input dir.
     pub fn get_checksum(input_dirs: &[String]) -> String {
         let mut hasher = Sha256::new();
         for dir in input_dirs {
+            // A remote repository is only known by its URL
+            if is_git_url(dir) {
+                hasher.update(dir.as_bytes());
+                continue;
+            }
             let base_path = Path::new(dir);
             if !base_path.exists() {
                 continue;
@@ -264,6 +1025,52 @@ impl YekConfig {
         hex[..8].to_owned()
     }
 
+    /// Settings that are valid but probably not what was meant, as "field: message" lines
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        // Incremental mode keeps its cache in output_dir, so that one still has a use
+        if self.stream && !self.incremental {
+            if let Some(output_dir) = &self.output_dir {
+                warnings.push(format!(
+                    "stream: output_dir '{}' is not used when streaming to stdout",
+                    output_dir
+                ));
+            }
+        }
+
+        if self.include_untracked && self.diff.is_none() {
+            warnings.push("include_untracked: not used without diff".to_string());
+        }
+
+        if self.sidecar && self.stream {
+            warnings.push("sidecar: not written when streaming to stdout".to_string());
+        }
+
+        if !self.redact && !self.redact_patterns.is_empty() {
+            warnings.push("redact_patterns: not used unless redact is set".to_string());
+        }
+
+        if self.file_separator.is_some() && (self.json || self.jsonl) {
+            warnings.push("file_separator: not used with json or jsonl".to_string());
+        }
+
+        if !self.tier_headers && !self.priority_tiers.is_empty() {
+            warnings.push("priority_tiers: not used unless tier_headers is set".to_string());
+        }
+
+        let max_size = self.max_size.trim();
+        if !self.token_mode && !max_size.is_empty() && max_size.chars().all(|c| c.is_ascii_digit())
+        {
+            warnings.push(format!(
+                "max_size: '{}' has no unit and is read as bytes, e.g. use \"{}KB\" for kilobytes",
+                max_size, max_size
+            ));
+        }
+
+        warnings
+    }
+
     /// Validate the final config.
     pub fn validate(&self) -> Result<()> {
         if !self.output_template.contains("FILE_PATH")
@@ -278,31 +1085,234 @@ impl YekConfig {
             return Err(anyhow!("max_size: cannot be 0"));
         }
 
+        if self.output_dir_name.is_empty()
+            || self.output_dir_name.contains(['/', '\\'])
+            || self.output_dir_name == "."
+            || self.output_dir_name == ".."
+        {
+            return Err(anyhow!(
+                "output_dir_name: '{}' must be a plain directory name",
+                self.output_dir_name
+            ));
+        }
+
+        if self.watch && self.from_stdin {
+            return Err(anyhow!(
+                "watch: can't be used with a file list read from stdin"
+            ));
+        }
+
+        if self.watch && self.input_dirs.iter().any(|dir| is_git_url(dir)) {
+            return Err(anyhow!("watch: can't be used with a remote repository URL"));
+        }
+
+        if self.absolute_paths && self.path_prefix.is_some() {
+            return Err(anyhow!(
+                "absolute_paths: can't be combined with path_prefix"
+            ));
+        }
+
+        // Clones of remote repositories are made in a temporary directory during the run
+        if self.absolute_paths && self.input_dirs.iter().any(|dir| is_git_url(dir)) {
+            return Err(anyhow!(
+                "absolute_paths: can't be used with a remote repository URL"
+            ));
+        }
+
+        if self.single_document && self.split_by.is_some() {
+            return Err(anyhow!("single_document: can't be combined with split_by"));
+        }
+
+        if self.skip_if_unchanged && self.split_by.is_some() {
+            return Err(anyhow!("skip_if_unchanged: can't be used with split_by"));
+        }
+
+        if self.json && self.jsonl {
+            return Err(anyhow!("jsonl: can't be combined with json"));
+        }
+
+        if self.tree_header && (self.json || self.jsonl) {
+            return Err(anyhow!("tree_header: can't be combined with json or jsonl"));
+        }
+
+        if self.tier_headers && (self.json || self.jsonl) {
+            return Err(anyhow!(
+                "tier_headers: can't be combined with json or jsonl"
+            ));
+        }
+
+        if let Some(prompt_file) = &self.prompt_file {
+            if self.json || self.jsonl {
+                return Err(anyhow!("prompt_file: can't be combined with json or jsonl"));
+            }
+            if !Path::new(prompt_file).is_file() {
+                return Err(anyhow!("prompt_file: {} is not a file", prompt_file));
+            }
+        }
+
+        if (self.paths_only || self.tree) && self.jsonl {
+            return Err(anyhow!("paths_only: can't be combined with jsonl"));
+        }
+
+        if self.max_files == Some(0) {
+            return Err(anyhow!("max_files: cannot be 0"));
+        }
+
         if !self.token_mode {
-            ByteSize::from_str(&self.max_size)
-                .map_err(|e| anyhow!("max_size: Invalid size format: {}", e))?;
-        } else if self.tokens.to_lowercase().ends_with('k') {
-            let val = self.tokens[..self.tokens.len() - 1]
-                .trim()
-                .parse::<usize>()
-                .map_err(|e| anyhow!("tokens: Invalid token size: {}", e))?;
-            if val == 0 {
-                return Err(anyhow!("tokens: cannot be 0"));
+            parse_size_input(&self.max_size, false)?;
+        } else if !self.tokens.is_empty() && parse_size_input(&self.tokens, true)? == 0 {
+            return Err(anyhow!("tokens: cannot be 0"));
+        }
+
+        parse_size_input(&self.max_scan_bytes, false).map_err(|_| {
+            anyhow!(
+                "max_scan_bytes: Invalid size format: '{}'",
+                self.max_scan_bytes
+            )
+        })?;
+
+        if let Some(max_total_size) = &self.max_total_size {
+            let size = parse_size_input(max_total_size, self.token_mode).map_err(|_| {
+                anyhow!("max_total_size: Invalid size format: '{}'", max_total_size)
+            })?;
+            if size == 0 {
+                return Err(anyhow!("max_total_size: cannot be 0"));
+            }
+        }
+
+        if let Some(max_file_size) = &self.max_file_size {
+            let size = parse_size_input(max_file_size, false)
+                .map_err(|_| anyhow!("max_file_size: Invalid size format: '{}'", max_file_size))?;
+            if size == 0 {
+                return Err(anyhow!("max_file_size: cannot be 0"));
+            }
+        }
+
+        if let Some(encoding) = &self.encoding {
+            if !encoding.eq_ignore_ascii_case("auto")
+                && encoding_rs::Encoding::for_label(encoding.as_bytes()).is_none()
+            {
+                return Err(anyhow!(
+                    "encoding: Unknown encoding '{}'. Expected \"auto\" or a label such as utf-8, latin1 or utf-16",
+                    encoding
+                ));
+            }
+        }
+
+        if !SUPPORTED_TOKENIZERS.contains(&self.tokenizer.as_str()) {
+            return Err(anyhow!(
+                "tokenizer: Unknown tokenizer '{}'. Expected one of: {}",
+                self.tokenizer,
+                SUPPORTED_TOKENIZERS.join(", ")
+            ));
+        }
+
+        if let Some(since) = &self.since {
+            parse_since(since)?;
+        }
+
+        if let Some(compress) = &self.compress {
+            if !SUPPORTED_COMPRESSIONS.contains(&compress.as_str()) {
+                return Err(anyhow!(
+                    "compress: Unknown compression '{}'. Expected one of: {}",
+                    compress,
+                    SUPPORTED_COMPRESSIONS.join(", ")
+                ));
+            }
+        }
+
+        if let Some(split_by) = &self.split_by {
+            if !SUPPORTED_SPLIT_BY.contains(&split_by.as_str()) {
+                return Err(anyhow!(
+                    "split_by: Unknown split '{}'. Expected one of: {}",
+                    split_by,
+                    SUPPORTED_SPLIT_BY.join(", ")
+                ));
+            }
+        }
+
+        if let Some(line_endings) = &self.normalize_line_endings {
+            if !SUPPORTED_LINE_ENDINGS.contains(&line_endings.as_str()) {
+                return Err(anyhow!(
+                    "normalize_line_endings: Unknown line ending '{}'. Expected one of: {}",
+                    line_endings,
+                    SUPPORTED_LINE_ENDINGS.join(", ")
+                ));
+            }
+        }
+
+        if !SUPPORTED_SORT_BY.contains(&self.sort_by.as_str()) {
+            return Err(anyhow!(
+                "sort_by: Unknown sort '{}'. Expected one of: {}",
+                self.sort_by,
+                SUPPORTED_SORT_BY.join(", ")
+            ));
+        }
+
+        if let Some(template) = &self.output_name_template {
+            let mut rest = template.as_str();
+            while let Some((name, after)) = rest
+                .split_once('{')
+                .and_then(|(_, after)| after.split_once('}'))
+            {
+                if !OUTPUT_NAME_PLACEHOLDERS.contains(&name) {
+                    return Err(anyhow!(
+                        "output_name_template: Unknown placeholder '{{{}}}'. Expected one of: {}",
+                        name,
+                        OUTPUT_NAME_PLACEHOLDERS
+                            .iter()
+                            .map(|p| format!("{{{}}}", p))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+                rest = after;
+            }
+            if self.split_by.is_some() && !template.contains("{group}") {
+                return Err(anyhow!(
+                    "output_name_template: must contain {{group}} when split_by is set"
+                ));
             }
-        } else if !self.tokens.is_empty() {
-            // parse as integer
-            let val = self
-                .tokens
-                .parse::<usize>()
-                .map_err(|e| anyhow!("tokens: Invalid token size: {}", e))?;
-            if val == 0 {
-                return Err(anyhow!("tokens: cannot be 0"));
+        }
+
+        if let Some(boost) = self.git_boost_max {
+            if !(0..=1000).contains(&boost) {
+                return Err(anyhow!(
+                    "git_boost_max: {} must be between 0 and 1000",
+                    boost
+                ));
             }
         }
 
-        // If not streaming, validate output directory
-        if !self.stream {
-            self.ensure_output_dir()?;
+        if !(0.0..=1.0).contains(&self.git_frequency_weight) {
+            return Err(anyhow!(
+                "git_frequency_weight: {} must be between 0.0 and 1.0",
+                self.git_frequency_weight
+            ));
+        }
+
+        if !(-1000..=1000).contains(&self.depth_priority) {
+            return Err(anyhow!(
+                "depth_priority: {} must be between -1000 and 1000",
+                self.depth_priority
+            ));
+        }
+
+        if self.max_line_length == Some(0) {
+            return Err(anyhow!("max_line_length: cannot be 0"));
+        }
+
+        if self.binary_scan_bytes == Some(0) {
+            return Err(anyhow!("binary_scan_bytes: cannot be 0"));
+        }
+
+        if let Some(tolerance) = self.null_byte_tolerance {
+            if !(0.0..=1.0).contains(&tolerance) {
+                return Err(anyhow!(
+                    "null_byte_tolerance: {} must be between 0.0 and 1.0",
+                    tolerance
+                ));
+            }
         }
 
         // Validate ignore patterns
@@ -311,17 +1321,79 @@ impl YekConfig {
                 .map_err(|e| anyhow!("ignore_patterns: Invalid pattern '{}': {}", pattern, e))?;
         }
 
+        // Validate the regexes of secrets to redact
+        Redactor::new(&self.redact_patterns)?;
+
+        // Validate include patterns
+        for pattern in &self.include_patterns {
+            glob::Pattern::new(pattern)
+                .map_err(|e| anyhow!("include_patterns: Invalid pattern '{}': {}", pattern, e))?;
+        }
+
+        if !SUPPORTED_PRIORITY_COMBINE.contains(&self.priority_combine.as_str()) {
+            return Err(anyhow!(
+                "priority_combine: Unknown mode '{}'. Expected one of: {}",
+                self.priority_combine,
+                SUPPORTED_PRIORITY_COMBINE.join(", ")
+            ));
+        }
+
+        if !SUPPORTED_PRIORITY_RULE_SYNTAXES.contains(&self.priority_rule_syntax.as_str()) {
+            return Err(anyhow!(
+                "priority_rule_syntax: Unknown syntax '{}'. Expected one of: {}",
+                self.priority_rule_syntax,
+                SUPPORTED_PRIORITY_RULE_SYNTAXES.join(", ")
+            ));
+        }
+
+        let mut tier_starts = HashSet::new();
+        for tier in &self.priority_tiers {
+            if tier.name.trim().is_empty() {
+                return Err(anyhow!("priority_tiers: tier name cannot be empty"));
+            }
+            if !tier_starts.insert(tier.min_priority) {
+                return Err(anyhow!(
+                    "priority_tiers: two tiers start at priority {}",
+                    tier.min_priority
+                ));
+            }
+        }
+
         // Validate priority rules
         for rule in &self.priority_rules {
-            if rule.score < 0 || rule.score > 1000 {
+            if !(-1000..=1000).contains(&rule.score) {
                 return Err(anyhow!(
-                    "priority_rules: Priority score {} must be between 0 and 1000",
+                    "priority_rules: Priority score {} must be between -1000 and 1000",
                     rule.score
                 ));
             }
-            glob::Pattern::new(&rule.pattern).map_err(|e| {
-                anyhow!("priority_rules: Invalid pattern '{}': {}", rule.pattern, e)
-            })?;
+            let syntax = rule.syntax.as_deref().unwrap_or(&self.priority_rule_syntax);
+            if !SUPPORTED_PRIORITY_RULE_SYNTAXES.contains(&syntax) {
+                return Err(anyhow!(
+                    "priority_rules: Unknown syntax '{}' for pattern '{}'. Expected one of: {}",
+                    syntax,
+                    rule.pattern,
+                    SUPPORTED_PRIORITY_RULE_SYNTAXES.join(", ")
+                ));
+            }
+            if syntax == "glob" {
+                compile_glob(&rule.pattern).map_err(|e| {
+                    anyhow!("priority_rules: Invalid glob '{}': {}", rule.pattern, e)
+                })?;
+            } else {
+                glob::Pattern::new(&rule.pattern).map_err(|e| {
+                    anyhow!("priority_rules: Invalid pattern '{}': {}", rule.pattern, e)
+                })?;
+            }
+            if let Some(exclude) = &rule.exclude_pattern {
+                regex::Regex::new(exclude).map_err(|e| {
+                    anyhow!(
+                        "priority_rules: Invalid exclude_pattern '{}': {}",
+                        exclude,
+                        e
+                    )
+                })?;
+            }
         }
 
         Ok(())
@@ -91,6 +91,9 @@ pub const BINARY_FILE_EXTENSIONS: &[&str] = &[
     "xap", // Windows Phone app
 ];
 
+/// Name of the output directory created in the system temp dir when none is configured
+pub const DEFAULT_OUTPUT_DIR_NAME: &str = "yek-output";
+
 /// Default sets of ignore patterns (separate from .gitignore)
 pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
     "LICENSE",
@@ -153,3 +156,105 @@ pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
 ];
 
 pub const DEFAULT_OUTPUT_TEMPLATE: &str = ">>>> FILE_PATH\nFILE_CONTENT";
+
+/// Output file name when no `output_name_template` is set
+pub const DEFAULT_OUTPUT_NAME_TEMPLATE: &str = "yek-output-{checksum}.{ext}";
+
+/// Output file name for each group when splitting and no `output_name_template` is set
+pub const DEFAULT_SPLIT_OUTPUT_NAME_TEMPLATE: &str = "yek-output-{checksum}-{group}.{ext}";
+
+/// Placeholders accepted by `output_name_template`
+pub const OUTPUT_NAME_PLACEHOLDERS: &[&str] = &["checksum", "repo", "date", "ext", "group"];
+
+/// Tokenizer used to count tokens when none is configured
+pub const DEFAULT_TOKENIZER: &str = "cl100k_base";
+
+/// Tokenizers accepted by the `tokenizer` option
+pub const SUPPORTED_TOKENIZERS: &[&str] = &[
+    "cl100k_base",
+    "o200k_base",
+    "p50k_base",
+    "r50k_base",
+    "whitespace",
+];
+
+/// Known models for the `model` option: name, tokenizer and context window in tokens.
+/// Models without a public tokenizer are counted with cl100k_base, which is close enough
+/// for sizing.
+pub const MODEL_PRESETS: &[(&str, &str, usize)] = &[
+    ("gpt-4o", "o200k_base", 128_000),
+    ("gpt-4o-mini", "o200k_base", 128_000),
+    ("gpt-4.1", "o200k_base", 1_047_576),
+    ("o1", "o200k_base", 200_000),
+    ("o3-mini", "o200k_base", 200_000),
+    ("gpt-4-turbo", "cl100k_base", 128_000),
+    ("gpt-4", "cl100k_base", 8_192),
+    ("gpt-3.5-turbo", "cl100k_base", 16_385),
+    ("claude-3-5-sonnet", "cl100k_base", 200_000),
+    ("claude-3-7-sonnet", "cl100k_base", 200_000),
+    ("claude-3-opus", "cl100k_base", 200_000),
+    ("gemini-1.5-pro", "cl100k_base", 2_000_000),
+];
+
+/// Most files a walk may find before it's aborted (`max_scan_files`)
+pub const DEFAULT_MAX_SCAN_FILES: usize = 100_000;
+
+/// Most bytes the files found by a walk may add up to before it's aborted (`max_scan_bytes`)
+pub const DEFAULT_MAX_SCAN_BYTES: &str = "2GB";
+
+/// Regexes of secrets masked by `redact`. When a pattern has a capture group, only the
+/// group is masked, so e.g. the variable name in `API_KEY=...` stays readable.
+pub const SECRET_PATTERNS: &[&str] = &[
+    // Private key blocks (PEM)
+    r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----",
+    // AWS access key IDs
+    r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b",
+    // GitHub, Slack, Google and OpenAI-style API keys
+    r"\bgh[pousr]_[A-Za-z0-9]{36,}\b",
+    r"\bxox[abprs]-[A-Za-z0-9-]{10,}",
+    r"\bAIza[0-9A-Za-z_-]{35}\b",
+    r"\bsk-[A-Za-z0-9_-]{20,}",
+    // Assignments to secret-looking names that take up the rest of the line, as in .env files
+    r#"(?im)\b[A-Z0-9_]*(?:API_?KEY|SECRET|TOKEN|PASSWORD|PASSWD)[A-Z0-9_]*["']?\s*[:=]\s*["']?([A-Za-z0-9_\-./+=]{8,})["']?[;,]?\s*$"#,
+];
+
+/// Shortest run of base64-like characters that `redact` checks for high entropy
+pub const REDACT_MIN_ENTROPY_LEN: usize = 32;
+
+/// Shannon entropy (bits per character) above which such a run is taken for a secret
+pub const REDACT_ENTROPY_THRESHOLD: f64 = 4.5;
+
+/// How many leading bytes of a file are scanned to decide if it is binary
+pub const DEFAULT_BINARY_SCAN_BYTES: usize = 8192;
+
+/// Share of null bytes in the scanned prefix above which a file is treated as binary
+pub const DEFAULT_NULL_BYTE_TOLERANCE: f64 = 0.1;
+
+/// How many of the most recent commits are read for commit times, counts and hashes
+pub const DEFAULT_MAX_GIT_DEPTH: i32 = 100;
+
+/// Compression formats accepted by `compress`
+pub const SUPPORTED_COMPRESSIONS: &[&str] = &["gzip"];
+
+/// Values accepted by `split_by`
+pub const SUPPORTED_SPLIT_BY: &[&str] = &["directory"];
+
+/// Line endings accepted by `normalize_line_endings`
+pub const SUPPORTED_LINE_ENDINGS: &[&str] = &["lf", "crlf", "none"];
+
+/// Output orders accepted by the `sort_by` option
+pub const SUPPORTED_SORT_BY: &[&str] = &["priority", "path", "size"];
+
+/// Pattern syntaxes of priority rules (`priority_rule_syntax` and a rule's `syntax`)
+pub const SUPPORTED_PRIORITY_RULE_SYNTAXES: &[&str] = &["regex", "glob"];
+
+/// Ways to combine the scores of several matching priority rules (`priority_combine`)
+pub const SUPPORTED_PRIORITY_COMBINE: &[&str] = &["sum", "max"];
+
+/// Tiers labelled with `tier_headers` when `priority_tiers` isn't set, as (name, lowest
+/// priority): the highest tier a file's priority reaches is the one it's in
+pub const DEFAULT_PRIORITY_TIERS: &[(&str, i32)] = &[
+    ("HIGH PRIORITY", 100),
+    ("MEDIUM PRIORITY", 50),
+    ("LOW PRIORITY", i32::MIN),
+];
@@ -2,90 +2,507 @@ use anyhow::anyhow;
 use anyhow::Result;
 use bytesize::ByteSize;
 use content_inspector::{inspect, ContentType};
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use flate2::{write::GzEncoder, Compression};
+use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
+use sha2::{Digest, Sha256};
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{self, Read},
-    path::Path,
-    str::FromStr,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, File},
+    io::{self, BufRead, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
     sync::OnceLock,
 };
 use tiktoken_rs::CoreBPE;
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Date, OffsetDateTime,
+};
 
+pub mod cache;
 pub mod config;
 pub mod defaults;
 pub mod parallel;
 pub mod priority;
+pub mod redact;
+pub mod remote;
+pub mod strip;
+pub mod watch;
 
-use config::YekConfig;
-use parallel::{process_files_parallel, ProcessedFile};
-use priority::compute_recentness_boost;
+use cache::{file_stamp, FileCache};
+use config::{OutputFormat, YekConfig};
+use defaults::{
+    DEFAULT_BINARY_SCAN_BYTES, DEFAULT_MAX_GIT_DEPTH, DEFAULT_NULL_BYTE_TOLERANCE,
+    DEFAULT_OUTPUT_NAME_TEMPLATE, DEFAULT_PRIORITY_TIERS, DEFAULT_SPLIT_OUTPUT_NAME_TEMPLATE,
+    DEFAULT_TOKENIZER,
+};
+use parallel::{
+    normalize_path, process_file_list, process_files_parallel_with, walk_files, BinaryFiles,
+    BinaryReason, ProcessedFile, ReadError, ReadErrors,
+};
+use priority::{
+    compute_git_boost, compute_recentness_boost, depth_priority, explain_file_priority,
+    get_changed_files_git2, get_file_mtimes, get_file_priority_with_extensions,
+    get_git_history_git2, get_head_commit_git2, get_tracked_files_git2, priority_tier,
+    with_default_syntax, GitHistory, PriorityTier,
+};
+use redact::Redactor;
+use remote::{clone_remote, is_git_url, repo_name_from_url};
+use strip::{collapse_blank_lines, strip_comments, truncate_long_lines};
 
-// Add a static BPE encoder for reuse
-static TOKENIZER: OnceLock<CoreBPE> = OnceLock::new();
+// Static BPE encoders for reuse, loaded lazily on first use
+static CL100K_BASE: OnceLock<CoreBPE> = OnceLock::new();
+static O200K_BASE: OnceLock<CoreBPE> = OnceLock::new();
+static P50K_BASE: OnceLock<CoreBPE> = OnceLock::new();
+static R50K_BASE: OnceLock<CoreBPE> = OnceLock::new();
 
-fn get_tokenizer() -> &'static CoreBPE {
-    TOKENIZER.get_or_init(|| {
-        tiktoken_rs::get_bpe_from_model("gpt-3.5-turbo").expect("Failed to load tokenizer")
-    })
+fn get_tokenizer(name: &str) -> Option<&'static CoreBPE> {
+    let (cell, load): (&OnceLock<CoreBPE>, fn() -> Result<CoreBPE>) = match name {
+        "cl100k_base" => (&CL100K_BASE, tiktoken_rs::cl100k_base),
+        "o200k_base" => (&O200K_BASE, tiktoken_rs::o200k_base),
+        "p50k_base" => (&P50K_BASE, tiktoken_rs::p50k_base),
+        "r50k_base" => (&R50K_BASE, tiktoken_rs::r50k_base),
+        _ => return None,
+    };
+    Some(cell.get_or_init(|| load().expect("Failed to load tokenizer")))
 }
 
 /// Check if a file is likely text or binary by reading only a small chunk.
 /// This avoids reading large files fully just to detect their type.
 pub fn is_text_file(path: &Path, user_binary_extensions: &[String]) -> io::Result<bool> {
     // If extension is known to be binary, skip quickly
-    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+    if let Some(ext) = file_extension(path) {
         if user_binary_extensions.iter().any(|bin_ext| bin_ext == ext) {
             return Ok(false);
         }
     }
 
     // Short partial read to check if it's binary or text
-    const INSPECTION_BYTES: usize = 8192;
     let mut file = File::open(path)?;
-    let mut buf = vec![0u8; INSPECTION_BYTES];
+    let mut buf = vec![0u8; DEFAULT_BINARY_SCAN_BYTES];
     let n = file.read(&mut buf)?;
     buf.truncate(n);
 
-    Ok(inspect(&buf) != ContentType::BINARY)
+    Ok(!is_binary_content(
+        &buf,
+        DEFAULT_BINARY_SCAN_BYTES,
+        DEFAULT_NULL_BYTE_TOLERANCE,
+    ))
+}
+
+/// The extension of `path`, if it has one that is valid UTF-8.
+fn file_extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|e| e.to_str())
+}
+
+/// Decode file content with an encoding label such as "utf-8", "latin1" or "utf-16".
+/// "auto" picks UTF-8 when the content is valid UTF-8 and Windows-1252 otherwise. A
+/// byte-order mark always takes precedence and is removed. Returns `None` for unknown
+/// labels and for content that is malformed in the chosen encoding.
+pub fn decode_text(content: &[u8], encoding: &str) -> Option<String> {
+    let encoding = if encoding.eq_ignore_ascii_case("auto") {
+        if std::str::from_utf8(content).is_ok() {
+            UTF_8
+        } else {
+            WINDOWS_1252
+        }
+    } else {
+        Encoding::for_label(encoding.as_bytes())?
+    };
+    let (text, _, had_errors) = encoding.decode(content);
+    (!had_errors).then(|| text.into_owned())
+}
+
+/// Decide if file content is binary by looking at its first `scan_bytes` bytes.
+/// Text with a UTF-16/UTF-32 byte-order mark is text, and known binary signatures
+/// (PDF, PNG) are binary. Otherwise content with null bytes is binary when their share
+/// exceeds `null_byte_tolerance`, or when the scanned bytes are not valid UTF-8.
+pub fn is_binary_content(content: &[u8], scan_bytes: usize, null_byte_tolerance: f64) -> bool {
+    let scanned = &content[..content.len().min(scan_bytes)];
+    let content_type = inspect(scanned);
+    if content_type != ContentType::BINARY && content_type != ContentType::UTF_8 {
+        // Byte-order mark, e.g. UTF-16 text which is full of null bytes
+        return false;
+    }
+
+    let nulls = scanned.iter().filter(|&&b| b == 0).count();
+    if nulls == 0 {
+        // No null bytes, but inspect may still know a binary signature
+        return content_type == ContentType::BINARY;
+    }
+
+    // A stray null in otherwise valid UTF-8 is tolerated. The scan may cut the
+    // last character short, which `error_len() == None` accounts for.
+    let valid_utf8 = match std::str::from_utf8(scanned) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    !valid_utf8 || nulls as f64 / scanned.len() as f64 > null_byte_tolerance
+}
+
+/// Read a newline-separated list of file paths, e.g. from `git diff --name-only`.
+/// Blank lines are skipped and Windows line endings are tolerated.
+pub fn read_file_list(reader: impl BufRead) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if !line.trim().is_empty() {
+            paths.push(PathBuf::from(line));
+        }
+    }
+    Ok(paths)
+}
+
+/// Read a NUL-separated list of file paths, e.g. from `git ls-files -z` or
+/// `find -print0`. Paths are taken as they are, spaces and newlines included.
+pub fn read_file_list0(mut reader: impl BufRead) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let mut entry = Vec::new();
+    while reader.read_until(b'\0', &mut entry)? > 0 {
+        if entry.last() == Some(&b'\0') {
+            entry.pop();
+        }
+        if !entry.is_empty() {
+            let path = String::from_utf8(std::mem::take(&mut entry))
+                .map_err(|e| anyhow!("stdin_filenames0: path is not valid UTF-8: {}", e))?;
+            paths.push(PathBuf::from(path));
+        }
+    }
+    Ok(paths)
+}
+
+/// Progress bar for the walk and read phase. Only shown on an interactive terminal,
+/// and never when streaming to stdout or with `quiet` set.
+fn progress_bar(config: &YekConfig) -> Option<ProgressBar> {
+    if config.stream || config.quiet || !io::stderr().is_terminal() {
+        return None;
+    }
+    let progress = ProgressBar::new(0);
+    progress.set_style(
+        ProgressStyle::with_template("{spinner} {pos}/{len} files {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+    );
+    Some(progress)
+}
+
+/// Keep only files changed at or after `since`. Files Git doesn't track have no
+/// commit time and are kept unless `exclude_untracked` is set.
+fn retain_changed_since(
+    files: &mut Vec<ProcessedFile>,
+    since: u64,
+    commit_times: &HashMap<String, u64>,
+    tracked: &HashSet<String>,
+    config: &YekConfig,
+) {
+    files.retain(|f| match commit_times.get(&f.rel_path) {
+        Some(time) => *time >= since,
+        None => !tracked.contains(&f.rel_path) && !config.exclude_untracked,
+    });
+}
+
+/// Without Git history, boost files by their modification time the way commit times would
+fn add_mtime_boost(files: &mut [ProcessedFile], base: &Path, max_boost: i32) {
+    let mtimes = get_file_mtimes(base, files.iter().map(|f| f.rel_path.as_str()));
+    let boost = compute_recentness_boost(&mtimes, max_boost);
+    for file in files {
+        file.priority += boost.get(&file.rel_path).copied().unwrap_or(0);
+    }
+}
+
+/// Record the commit hash of each file that has one in `hashes`, keyed by relative path
+fn set_commit_hashes(files: &mut [ProcessedFile], hashes: &HashMap<String, String>) {
+    for file in files {
+        file.commit = hashes.get(&file.rel_path).cloned();
+    }
+}
+
+/// Path prefix for the files of each input directory: none with a single directory,
+/// otherwise the directory's name, with a numeric suffix when two names are the same.
+fn input_dir_labels(input_dirs: &[String]) -> Vec<Option<String>> {
+    if input_dirs.len() < 2 {
+        return vec![None; input_dirs.len()];
+    }
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    input_dirs
+        .iter()
+        .map(|dir| {
+            let path = Path::new(dir);
+            let name = path
+                .canonicalize()
+                .ok()
+                .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+                .unwrap_or_else(|| normalize_path(path, Path::new("")));
+            let count = seen.entry(name.clone()).or_insert(0);
+            *count += 1;
+            Some(if *count == 1 {
+                name
+            } else {
+                format!("{}-{}", name, count)
+            })
+        })
+        .collect()
+}
+
+/// `rel_path` without the input directory label in front (see `input_dir_labels`), as it
+/// is within its input directory
+fn input_relative_path<'a>(rel_path: &'a str, config: &YekConfig) -> &'a str {
+    if config.from_stdin || config.input_dirs.len() < 2 {
+        return rel_path;
+    }
+    rel_path.split_once('/').map_or(rel_path, |(_, rest)| rest)
 }
 
 /// Main entrypoint for serialization, used by CLI and tests
 pub fn serialize_repo(config: &YekConfig) -> Result<(String, Vec<ProcessedFile>)> {
-    // Gather commit times from each input dir
-    let combined_commit_times = config
-        .input_dirs
-        .par_iter()
-        .filter_map(|dir| {
-            let repo_path = Path::new(dir);
-            priority::get_recent_commit_times_git2(
-                repo_path,
-                config.max_git_depth.try_into().unwrap_or(0),
-            )
-        })
-        .flatten()
-        .collect::<HashMap<String, u64>>();
+    let files = collect_files(config)?;
+    Ok((serialize_files(&files, config)?, files))
+}
 
-    // Compute a recentness-based boost
-    let recentness_boost =
-        compute_recentness_boost(&combined_commit_times, config.git_boost_max.unwrap_or(100));
+/// The output of `serialize_repo` for already collected files
+fn serialize_files(files: &[ProcessedFile], config: &YekConfig) -> Result<String> {
+    // In dry-run mode only report what would be included
+    if config.dry_run {
+        return dry_run_summary(files, config);
+    }
 
-    // Process files in parallel for each directory
-    let merged_files = config
+    if config.count_only {
+        return count_summary(files, config);
+    }
+
+    if config.explain {
+        return explain_summary(files, config);
+    }
+
+    // Build the final output string
+    concat_files(files, config)
+}
+
+/// Read, filter and rank the files of the input directories (or the file list on stdin)
+/// without rendering any output. Files come in output order, most important last.
+pub fn collect_files(config: &YekConfig) -> Result<Vec<ProcessedFile>> {
+    Ok(collect_files_with_errors(config)?.files)
+}
+
+/// Files returned by `collect_files_with_errors`, with what happened along the way
+#[derive(Debug, Clone, Default)]
+pub struct CollectedFiles {
+    /// The files, in output order
+    pub files: Vec<ProcessedFile>,
+    /// Files that couldn't be read and were skipped, with the error, sorted by path
+    pub read_errors: Vec<ReadError>,
+    /// Secrets masked in the files' content with `redact`
+    pub redactions: usize,
+    /// Files skipped as binary, with the reason, sorted by path. Only filled with
+    /// `list_binary` (see `binary_files_report`).
+    pub binary_files: Vec<(String, BinaryReason)>,
+    /// URL and path of each remote repository clone kept with `keep_clone`
+    pub kept_clones: Vec<(String, String)>,
+}
+
+/// Same as `collect_files`, but also reports the files that couldn't be read and the
+/// number of secrets redacted
+pub fn collect_files_with_errors(config: &YekConfig) -> Result<CollectedFiles> {
+    let max_commits = config
+        .max_git_depth
+        .unwrap_or(DEFAULT_MAX_GIT_DEPTH)
+        .try_into()
+        .unwrap_or(0);
+    let git_boost_max = config.git_boost_max.unwrap_or(100);
+    let since = config.since.as_deref().map(parse_since).transpose()?;
+
+    // Remote repositories are cloned first, and removed again when the clones go out of scope
+    let clones = config
         .input_dirs
-        .par_iter()
+        .iter()
         .map(|dir| {
-            let path = Path::new(dir);
-            process_files_parallel(path, config, &recentness_boost)
+            is_git_url(dir)
+                .then(|| clone_remote(dir, config.keep_clone))
+                .transpose()
         })
-        .collect::<Result<Vec<Vec<ProcessedFile>>>>()?
-        .into_iter()
-        .flatten()
-        .collect::<Vec<ProcessedFile>>();
+        .collect::<Result<Vec<_>>>()?;
+    let input_dirs = config
+        .input_dirs
+        .iter()
+        .zip(&clones)
+        .map(|(dir, clone)| match clone {
+            Some(clone) => clone.path().to_string_lossy().to_string(),
+            None => dir.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    // In incremental mode, unchanged files are taken from the cache in the output directory.
+    // Listing binaries needs every file read, as the cache doesn't know why a file was skipped.
+    let cache = match &config.output_dir {
+        Some(dir) if config.incremental && !config.list_binary => {
+            Some(FileCache::load(Path::new(dir), config))
+        }
+        _ => None,
+    };
+    let binaries = config.list_binary.then(BinaryFiles::default);
+    let read_errors = ReadErrors::default();
+
+    let progress = progress_bar(config);
+
+    let files = if config.from_stdin {
+        // Gather the Git history of each input dir
+        let history = input_dirs
+            .par_iter()
+            .filter_map(|dir| get_git_history_git2(Path::new(dir), max_commits))
+            .reduce(GitHistory::default, |mut all, history| {
+                all.extend(history);
+                all
+            });
+        let recentness_boost = compute_git_boost(
+            &history.times,
+            &history.counts,
+            git_boost_max,
+            config.git_frequency_weight,
+        );
+
+        // Process exactly the files listed on stdin, relative to the current directory
+        let paths = if config.stdin_filenames0 {
+            read_file_list0(io::stdin().lock())?
+        } else {
+            read_file_list(io::stdin().lock())?
+        };
+        let current_dir = std::env::current_dir()?;
+        let mut files = process_file_list(
+            &current_dir,
+            &paths,
+            config,
+            &recentness_boost,
+            binaries.as_ref(),
+            Some(&read_errors),
+        )?;
+        if history.times.is_empty() && config.use_mtime {
+            add_mtime_boost(&mut files, &current_dir, git_boost_max);
+        }
+        if config.show_commit {
+            set_commit_hashes(&mut files, &history.hashes);
+        }
+        if let Some(since) = since {
+            let tracked = input_dirs
+                .par_iter()
+                .filter_map(|dir| get_tracked_files_git2(Path::new(dir)))
+                .flatten()
+                .collect::<HashSet<String>>();
+            retain_changed_since(&mut files, since, &history.times, &tracked, config);
+        }
+        if let Some(base) = &config.diff {
+            let changed = get_changed_files_git2(&current_dir, base, config.include_untracked)?;
+            files.retain(|f| changed.contains(&f.rel_path));
+        }
+        files
+    } else {
+        // Process each directory in parallel, with its own Git history. With several
+        // directories, paths are prefixed with the directory name so they don't collide.
+        let labels = input_dir_labels(&input_dirs);
+        input_dirs
+            .par_iter()
+            .zip(labels.par_iter())
+            .map(|(dir, label)| {
+                let path = Path::new(dir);
+                let history = get_git_history_git2(path, max_commits).unwrap_or_default();
+                let recentness_boost = compute_git_boost(
+                    &history.times,
+                    &history.counts,
+                    git_boost_max,
+                    config.git_frequency_weight,
+                );
+                let dir_binaries = binaries.as_ref().map(|_| BinaryFiles::default());
+                let dir_read_errors = ReadErrors::default();
+                let mut files = process_files_parallel_with(
+                    path,
+                    config,
+                    &recentness_boost,
+                    cache.as_ref(),
+                    progress.as_ref(),
+                    dir_binaries.as_ref(),
+                    Some(&dir_read_errors),
+                )?;
+                if history.times.is_empty() && config.use_mtime {
+                    add_mtime_boost(&mut files, path, git_boost_max);
+                }
+                if config.show_commit {
+                    set_commit_hashes(&mut files, &history.hashes);
+                }
+                if let Some(since) = since {
+                    let tracked = get_tracked_files_git2(path).unwrap_or_default();
+                    retain_changed_since(&mut files, since, &history.times, &tracked, config);
+                }
+                if let Some(base) = &config.diff {
+                    let changed = get_changed_files_git2(path, base, config.include_untracked)?;
+                    files.retain(|f| changed.contains(&f.rel_path));
+                }
+                if let Some(label) = label {
+                    for file in &mut files {
+                        file.rel_path = format!("{}/{}", label, file.rel_path);
+                    }
+                }
+                if let (Some(binaries), Some(dir_binaries)) = (&binaries, dir_binaries) {
+                    for (rel_path, reason) in dir_binaries.into_sorted() {
+                        let rel_path = match label {
+                            Some(label) => format!("{}/{}", label, rel_path),
+                            None => rel_path,
+                        };
+                        binaries.record(rel_path, reason);
+                    }
+                }
+                for (rel_path, error) in dir_read_errors.into_sorted() {
+                    let rel_path = match label {
+                        Some(label) => format!("{}/{}", label, rel_path),
+                        None => rel_path,
+                    };
+                    read_errors.record(rel_path, error);
+                }
+                Ok(files)
+            })
+            .collect::<Result<Vec<Vec<ProcessedFile>>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<ProcessedFile>>()
+    };
+
+    if let Some(progress) = &progress {
+        progress.finish_and_clear();
+    }
 
-    let mut files = merged_files;
+    if let Some(cache) = &cache {
+        tracing::debug!(
+            "Read {} changed files, the rest came from the cache",
+            cache.reads()
+        );
+        cache.save()?;
+    }
 
+    let kept_clones = config
+        .input_dirs
+        .iter()
+        .zip(&clones)
+        .filter(|_| config.keep_clone)
+        .filter_map(|(url, clone)| {
+            let path = clone.as_ref()?.path().to_string_lossy().to_string();
+            Some((url.clone(), path))
+        })
+        .collect();
+    let (files, redactions) = finish_files(files, config)?;
+    Ok(CollectedFiles {
+        files,
+        read_errors: read_errors.into_sorted(),
+        redactions,
+        binary_files: binaries.map(BinaryFiles::into_sorted).unwrap_or_default(),
+        kept_clones,
+    })
+}
+
+/// Put collected files in output order, drop the ones under min_priority or over
+/// max_files and redact secrets. Returns the files and the number of secrets redacted.
+fn finish_files(
+    mut files: Vec<ProcessedFile>,
+    config: &YekConfig,
+) -> Result<(Vec<ProcessedFile>, usize)> {
     // Sort final (priority asc, then file_index asc)
     files.par_sort_by(|a, b| {
         a.priority
@@ -93,111 +510,1904 @@ pub fn serialize_repo(config: &YekConfig) -> Result<(String, Vec<ProcessedFile>)
             .then_with(|| a.rel_path.cmp(&b.rel_path))
     });
 
-    // Build the final output string
-    let output_string = concat_files(&files, config)?;
+    // Drop files whose final priority (rules plus boost) is under the threshold
+    if let Some(min_priority) = config.min_priority {
+        files.retain(|f| f.priority >= min_priority);
+    }
 
-    // Only count tokens if debug logging is enabled
-    if tracing::Level::DEBUG <= tracing::level_filters::STATIC_MAX_LEVEL {
-        tracing::debug!("{} tokens generated", count_tokens(&output_string));
+    // Keep only the highest-priority files, which sort last
+    if let Some(max_files) = config.max_files {
+        let excess = files.len().saturating_sub(max_files);
+        files.drain(..excess);
     }
 
-    Ok((output_string, files))
+    // Mask secrets before anything is measured or rendered
+    let redactions = if config.redact {
+        let redactor = Redactor::new(&config.redact_patterns)?;
+        files
+            .par_iter_mut()
+            .map(|file| {
+                let (content, count) = redactor.redact(&file.content);
+                if let Cow::Owned(content) = content {
+                    file.content = content;
+                }
+                count
+            })
+            .sum()
+    } else {
+        0
+    };
+    Ok((files, redactions))
 }
 
-pub fn concat_files(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Result<String> {
-    let mut accumulated = 0_usize;
+/// Same as `serialize_repo`, but for files given as (relative path, content) pairs
+/// instead of read from the input directories, e.g. to test or embed the ranking and
+/// rendering without touching disk. Nothing is walked, ignored or checked for binary
+/// content, and there is no Git boost; priority rules, min_priority, max_files, redaction
+/// and the size cap apply as usual. Pass the returned files to `render_chunks` to split them.
+pub fn serialize_in_memory(
+    files: Vec<(String, String)>,
+    config: &YekConfig,
+) -> Result<(String, Vec<ProcessedFile>)> {
+    let rules = with_default_syntax(&config.priority_rules, &config.priority_rule_syntax);
+    let files = files
+        .into_iter()
+        .enumerate()
+        .map(|(file_index, (rel_path, content))| ProcessedFile {
+            priority: get_file_priority_with_extensions(
+                &rel_path,
+                &rules,
+                &config.extension_priorities,
+                &config.priority_combine,
+            ) + depth_priority(&rel_path, config.depth_priority),
+            file_index,
+            rel_path,
+            content,
+            commit: None,
+        })
+        .collect();
+    let (files, _) = finish_files(files, config)?;
+    Ok((serialize_files(&files, config)?, files))
+}
+
+/// Figures about a serialization run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SerializeStats {
+    /// Files in the output
+    pub total_files: usize,
+    /// Size of the output in bytes
+    pub total_bytes: usize,
+    /// Tokens in the output, counted with the configured tokenizer
+    pub total_tokens: usize,
+    /// Files that were read but left out because they didn't fit under the size or token cap
+    pub skipped_files: usize,
+    /// Files left out because they couldn't be read
+    pub unreadable_files: usize,
+    /// Secrets masked with `redact`
+    pub redactions: usize,
+}
+
+/// Same as `serialize_repo`, but also returns stats about the output
+pub fn serialize_repo_with_stats(
+    config: &YekConfig,
+) -> Result<(String, Vec<ProcessedFile>, SerializeStats)> {
+    let collected = collect_files_with_errors(config)?;
+    let files = collected.files;
+    let output = serialize_files(&files, config)?;
+    let stats = SerializeStats {
+        unreadable_files: collected.read_errors.len(),
+        redactions: collected.redactions,
+        ..serialize_stats(&output, &files, config)?
+    };
+    Ok((output, files, stats))
+}
+
+/// Stats for `output` as returned by `serialize_repo` for `files` and `config`.
+/// Unreadable files and redactions aren't known from these, so `unreadable_files` and
+/// `redactions` are 0.
+pub fn serialize_stats(
+    output: &str,
+    files: &[ProcessedFile],
+    config: &YekConfig,
+) -> Result<SerializeStats> {
+    // A count-only summary lists every file, anything else only the files under the cap
+    let total_files = if config.count_only {
+        files.len()
+    } else {
+        select_files(files, config)?.len()
+    };
+    Ok(SerializeStats {
+        total_files,
+        total_bytes: output.len(),
+        total_tokens: count_tokens_with(output, &config.tokenizer),
+        skipped_files: files.len() - total_files,
+        unreadable_files: 0,
+        redactions: 0,
+    })
+}
+
+/// One piece of rendered output: everything, or one group of files with split_by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    /// Position of the chunk in the output, from 0
+    pub index: usize,
+    /// Group the chunk holds (see `split_by_directory`), or `None` when not splitting
+    pub group: Option<String>,
+    /// Relative paths of the files in the chunk, in output order
+    pub files: Vec<String>,
+    /// Priorities of the files, in the order of `files`
+    pub priorities: Vec<i32>,
+    /// Byte offset in `content` where each file's entry starts, in the order of `files`.
+    /// `None` for output without per-file entries (summaries, paths only, JSON, JSON Lines).
+    pub offsets: Option<Vec<usize>>,
+    /// The rendered output
+    pub content: String,
+}
+
+/// Serialize the input directories into chunks without writing anything. See `render_chunks`.
+pub fn build_chunks(config: &YekConfig) -> Result<Vec<Chunk>> {
+    render_chunks(&collect_files(config)?, config)
+}
+
+/// Render collected files into chunks: one per group with split_by, otherwise a single
+/// chunk. Splitting has no effect when streaming or copying to the clipboard. The size cap
+/// applies per chunk, and max_total_size to all of them together, except with
+/// single_document, which always gives one uncapped chunk.
+/// Dry-run, count-only and explain summaries come back as a single chunk too.
+pub fn render_chunks(files: &[ProcessedFile], config: &YekConfig) -> Result<Vec<Chunk>> {
+    let paths = |files: &[&ProcessedFile]| files.iter().map(|f| f.rel_path.clone()).collect();
+    let priorities = |files: &[&ProcessedFile]| files.iter().map(|f| f.priority).collect();
+
+    let splitting =
+        config.split_by.is_some() && !config.single_document && !config.stream && !config.clipboard;
+    // Chunks are capped one by one, so the highest-priority files that fit under
+    // max_total_size are picked across all of them first
+    let within_total;
+    let files = match &config.max_total_size {
+        Some(total) if splitting && !config.count_only => {
+            let total = parse_size_input(total, config.token_mode)?;
+            // Each file is counted with the highest chunk number, and tier banners by group
+            let groups = files
+                .iter()
+                .map(|f| split_group(&f.rel_path))
+                .collect::<HashSet<_>>();
+            let extras =
+                ChunkExtras::new(true, true, Some(groups.len()), files.len(), total, config)?;
+            within_total = select_files_under(files, extras, total, config)?
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>();
+            &within_total[..]
+        }
+        _ => files,
+    };
+
+    if config.dry_run || config.count_only || config.explain {
+        let (content, included) = if config.dry_run {
+            (
+                dry_run_summary(files, config)?,
+                select_files(files, config)?,
+            )
+        } else if config.count_only {
+            (count_summary(files, config)?, files.iter().collect())
+        } else {
+            (
+                explain_summary(files, config)?,
+                select_files(files, config)?,
+            )
+        };
+        return Ok(vec![Chunk {
+            index: 0,
+            group: None,
+            files: paths(&included),
+            priorities: priorities(&included),
+            offsets: None,
+            content,
+        }]);
+    }
+
+    let render = |index: usize,
+                  group: Option<String>,
+                  files_to_include: &[&ProcessedFile],
+                  tree_files: Option<&[&ProcessedFile]>,
+                  toc: Option<&[String]>| {
+        let (content, offsets) =
+            render_selected_with_offsets(files_to_include, tree_files, toc, config)?;
+        Ok(Chunk {
+            index,
+            group,
+            files: paths(files_to_include),
+            priorities: priorities(files_to_include),
+            offsets,
+            content,
+        })
+    };
+    let mut chunks = if splitting {
+        let groups = split_by_directory(files);
+        let selected = select_split_files(&groups, config)?;
+        // The directory tree at the top of the first chunk shows the files of all of them,
+        // and so does the table of contents, with the chunk (from 1) each file is in
+        let all_files: Vec<&ProcessedFile> = selected.iter().flatten().copied().collect();
+        let toc: Vec<String> = selected
+            .iter()
+            .enumerate()
+            .flat_map(|(index, files_to_include)| {
+                toc_entries(files_to_include, config)
+                    .into_iter()
+                    .map(move |entry| format!("{} (chunk {})", entry, index + 1))
+            })
+            .collect();
+        groups
+            .iter()
+            .zip(&selected)
+            .enumerate()
+            .map(|(index, ((group, _), files_to_include))| {
+                let tree_files = (config.tree_header && index == 0).then_some(&all_files[..]);
+                let toc = (config.toc && index == 0).then_some(&toc[..]);
+                render(
+                    index,
+                    Some(group.clone()),
+                    files_to_include,
+                    tree_files,
+                    toc,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        let files_to_include = select_files(files, config)?;
+        let tree_files = config.tree_header.then_some(&files_to_include[..]);
+        let toc = config.toc.then(|| toc_entries(&files_to_include, config));
+        vec![render(
+            0,
+            None,
+            &files_to_include,
+            tree_files,
+            toc.as_deref(),
+        )?]
+    };
+    frame_chunks(&mut chunks, prompt_block(config)?.as_deref(), config);
+    Ok(chunks)
+}
+
+/// header_template or footer_template with {file_count}, {repo_name} and {total_tokens}
+/// filled in. `total_tokens` is only called when the template uses it.
+fn frame_template(
+    template: &str,
+    file_count: usize,
+    config: &YekConfig,
+    total_tokens: impl FnOnce() -> usize,
+) -> String {
+    let mut rendered = template
+        .replace("{file_count}", &file_count.to_string())
+        .replace("{repo_name}", &repo_name(config));
+    if rendered.contains("{total_tokens}") {
+        rendered = rendered.replace("{total_tokens}", &total_tokens().to_string());
+    }
+    rendered.replace("\\n", "\n")
+}
+
+/// Put the `prompt` (see `prompt_block`) and the header at the start of the first chunk
+/// and the footer at the end of the last one. The header and footer count the files and
+/// tokens of all the chunks. Nothing is added to output without per-file entries.
+fn frame_chunks(chunks: &mut [Chunk], prompt: Option<&str>, config: &YekConfig) {
+    if chunks.iter().any(|chunk| chunk.offsets.is_none()) {
+        return;
+    }
+    let file_count: usize = chunks.iter().map(|chunk| chunk.files.len()).sum();
+    let render = |template: &str| {
+        frame_template(template, file_count, config, || {
+            chunks
+                .iter()
+                .map(|chunk| count_tokens_with(&chunk.content, &config.tokenizer))
+                .sum()
+        })
+    };
+    let header = config.header_template.as_deref().map(render);
+    let footer = config.footer_template.as_deref().map(render);
+
+    if let (Some(footer), Some(last)) = (footer, chunks.last_mut()) {
+        last.content = format!("{}\n{}", last.content, footer);
+    }
+    if let Some(first) = chunks.first_mut() {
+        let mut preamble = prompt.unwrap_or_default().to_string();
+        if let Some(header) = header {
+            preamble = format!("{}{}\n", preamble, header);
+        }
+        first.content.insert_str(0, &preamble);
+        for offset in first.offsets.iter_mut().flatten() {
+            *offset += preamble.len();
+        }
+    }
+}
+
+/// Write each chunk to its own file in `output_dir`, named by `output_file_name` with
+/// `checksum` and the chunk's group, gzip-compressed (with ".gz" added) when `compress`
+/// is set. With `sidecar`, each also gets a ".meta.json" file (see `chunk_sidecar`).
+/// The files are written in parallel. Returns the chunks' paths in chunk order.
+pub fn write_chunks(
+    chunks: &[Chunk],
+    output_dir: &Path,
+    checksum: &str,
+    config: &YekConfig,
+) -> Result<Vec<String>> {
+    let suffix = if config.compress.is_some() { ".gz" } else { "" };
+    let paths = chunks
+        .iter()
+        .map(|chunk| {
+            let file_name = output_file_name(config, checksum, chunk.group.as_deref());
+            output_dir
+                .join(format!("{}{}", file_name, suffix))
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+
+    // Two chunks must never write the same file, also on case-insensitive file systems
+    let mut seen = HashMap::new();
+    for (path, chunk) in paths.iter().zip(chunks) {
+        if let Some(other) = seen.insert(path.to_lowercase(), chunk) {
+            return Err(anyhow!(
+                "output_name_template: chunks {} and {} would both be written to {}",
+                other.index,
+                chunk.index,
+                path
+            ));
+        }
+    }
+
+    chunks
+        .par_iter()
+        .zip(paths.par_iter())
+        .try_for_each(|(chunk, path)| -> Result<()> {
+            match &config.compress {
+                Some(compress) => {
+                    std::fs::write(path, compress_output(chunk.content.as_bytes(), compress)?)?
+                }
+                None => std::fs::write(path, chunk.content.as_bytes())?,
+            }
+            if config.sidecar {
+                let path = Path::new(path.strip_suffix(".gz").unwrap_or(path));
+                std::fs::write(
+                    path.with_extension("meta.json"),
+                    chunk_sidecar(chunk, config)?,
+                )?;
+            }
+            Ok(())
+        })?;
+    Ok(paths)
+}
+
+/// Metadata of a chunk as JSON, for tools that post-process chunk files: each file's path
+/// as shown in the chunk, its priority, and the byte offset in the (uncompressed) chunk
+/// where its entry starts, null when the output format has no per-file entries
+pub fn chunk_sidecar(chunk: &Chunk, config: &YekConfig) -> Result<String> {
+    let files = chunk
+        .files
+        .iter()
+        .zip(&chunk.priorities)
+        .enumerate()
+        .map(|(i, (path, priority))| {
+            serde_json::json!({
+                "path": display_path(path, config),
+                "priority": priority,
+                "offset": chunk.offsets.as_ref().map(|offsets| offsets[i]),
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(serde_json::to_string_pretty(&serde_json::json!({
+        "index": chunk.index,
+        "group": chunk.group,
+        "files": files,
+    }))?)
+}
+
+/// Stats of rendered chunks, summed over all of them. `files` are the collected files
+/// the chunks were rendered from. `unreadable_files` and `redactions` are 0, those counts
+/// come from `collect_files_with_errors`. Tokens are only counted in token mode, as
+/// tokenizing the whole output is slow; `total_tokens` is 0 in byte mode.
+pub fn chunk_stats(
+    chunks: &[Chunk],
+    files: &[ProcessedFile],
+    config: &YekConfig,
+) -> SerializeStats {
+    let total_files = chunks.iter().map(|c| c.files.len()).sum();
+    SerializeStats {
+        total_files,
+        total_bytes: chunks.iter().map(|c| c.content.len()).sum(),
+        total_tokens: if config.token_mode {
+            chunks
+                .iter()
+                .map(|c| count_tokens_with(&c.content, &config.tokenizer))
+                .sum()
+        } else {
+            0
+        },
+        skipped_files: files.len().saturating_sub(total_files),
+        unreadable_files: 0,
+        redactions: 0,
+    }
+}
+
+/// File content as it appears in the output: comments stripped, blank lines collapsed,
+/// line numbers added and line endings normalized when enabled.
+fn file_content<'a>(file: &'a ProcessedFile, config: &YekConfig) -> Cow<'a, str> {
+    let line_endings = config.normalize_line_endings.as_deref();
+    let mut content = Cow::Borrowed(file.content.as_str());
+    // Work on LF line endings; CRLF ones are put back at the end
+    if matches!(line_endings, Some("lf" | "crlf")) && content.contains("\r\n") {
+        content = Cow::Owned(content.replace("\r\n", "\n"));
+    }
+    if config.strip_comments {
+        if let Some(stripped) = strip_comments(&content, Path::new(&file.rel_path)) {
+            content = Cow::Owned(stripped);
+        }
+    }
+    if config.collapse_blank_lines {
+        content = Cow::Owned(collapse_blank_lines(&content));
+    }
+    if let Some(max) = config.max_line_length {
+        if let Some(truncated) = truncate_long_lines(&content, max) {
+            content = Cow::Owned(truncated);
+        }
+    }
+    if config.line_numbers {
+        let mut numbered = content
+            .lines()
+            .enumerate()
+            .map(|(i, line)| format!("{:>5} | {}", i + 1, line))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if content.ends_with('\n') {
+            numbered.push('\n');
+        }
+        content = Cow::Owned(numbered);
+    }
+    if line_endings == Some("crlf") {
+        content = Cow::Owned(content.replace('\n', "\r\n"));
+    }
+    content
+}
+
+/// Pick the files that fit under the configured size or token cap. Files are added from
+/// the highest priority down, and once one doesn't fit the lower-priority remainder is
+/// dropped. With single_document every file is kept. Returns the kept files in output order (priority ascending).
+/// The prompt, header, footer, table of contents, directory tree and tier banners count
+/// towards the cap (see `ChunkExtras`).
+fn select_files<'a>(
+    files: &'a [ProcessedFile],
+    config: &YekConfig,
+) -> anyhow::Result<Vec<&'a ProcessedFile>> {
+    let cap = size_cap(config)?;
+    let extras = ChunkExtras::new(true, true, None, files.len(), cap, config)?;
+    select_files_under(files, extras, cap, config)
+}
+
+/// What a chunk holds besides its file entries, counted towards the size cap along with
+/// them by `select_files_under`
+struct ChunkExtras {
+    /// Bytes or tokens taken before any file is picked: the prompt, header and footer,
+    /// the start of the table of contents and directory tree, and the lines files of other
+    /// chunks have in those
+    reserved: usize,
+    /// Whether each file has a line in the table of contents
+    toc: bool,
+    /// Chunk number shown after a file's table of contents line when splitting. Tier
+    /// banners are also told apart by split group then.
+    chunk_number: Option<usize>,
+    /// Directories listed in the directory tree so far, when there is one
+    tree_dirs: Option<HashSet<String>>,
+    /// Priority tiers, when each one starts with a banner
+    tiers: Option<Vec<PriorityTier>>,
+    /// Group and name of the tiers that have had a banner so far
+    banners: HashSet<(String, String)>,
+}
+
+impl ChunkExtras {
+    /// Extras of a chunk: the first one starts with the prompt, header, table of contents
+    /// and directory tree, and the last one ends with the footer. The header and footer
+    /// are measured with `file_count` files and, for {total_tokens}, `cap` tokens, which is
+    /// at least as many digits as they'll have.
+    fn new(
+        first: bool,
+        last: bool,
+        chunk_number: Option<usize>,
+        file_count: usize,
+        cap: usize,
+        config: &YekConfig,
+    ) -> Result<Self> {
+        let entries = !(config.paths_only || config.tree || config.jsonl);
+        let text = entries && !config.json;
+        let mut fixed = String::new();
+        if first && text {
+            if let Some(prompt) = prompt_block(config)? {
+                fixed.push_str(&prompt);
+            }
+            if let Some(header) = &config.header_template {
+                fixed.push_str(&frame_template(header, file_count, config, || cap));
+                fixed.push('\n');
+            }
+            if config.tree_header {
+                fixed.push_str("Directory tree:\n\n");
+            }
+        }
+        let toc = first && entries && config.toc;
+        if toc {
+            fixed.push_str(if text {
+                "Table of contents:\n\n"
+            } else {
+                "  \"toc\": [\n  ],\n"
+            });
+        }
+        if let (true, true, Some(footer)) = (last, text, &config.footer_template) {
+            fixed.push('\n');
+            fixed.push_str(&frame_template(footer, file_count, config, || cap));
+        }
+        Ok(ChunkExtras {
+            reserved: measured_size(&fixed, config),
+            toc,
+            chunk_number,
+            tree_dirs: (first && text && config.tree_header).then(HashSet::new),
+            tiers: (text && config.tier_headers).then(|| priority_tiers(config)),
+            banners: HashSet::new(),
+        })
+    }
+
+    /// Size of the lines `file` adds to the table of contents and the directory tree
+    fn listing_size(&self, file: &ProcessedFile, config: &YekConfig) -> usize {
+        let path = display_path(&file.rel_path, config);
+        let mut lines = String::new();
+        if self.toc {
+            let entry = match self.chunk_number {
+                Some(number) => format!("{} (chunk {})", path, number),
+                None => path.clone(),
+            };
+            if config.json {
+                lines = format!("    {},\n", serde_json::Value::from(entry));
+            } else {
+                lines = format!("- {}\n", entry);
+            }
+        }
+        // Each directory is listed once, above the first of its files
+        if let Some(tree_dirs) = &self.tree_dirs {
+            let parts: Vec<&str> = path.split('/').collect();
+            let (name, dirs) = parts.split_last().unwrap_or((&"", &[]));
+            for depth in 0..dirs.len() {
+                if !tree_dirs.contains(&dirs[..=depth].join("/")) {
+                    lines.push_str(&format!("{}{}/\n", "  ".repeat(depth), dirs[depth]));
+                }
+            }
+            lines.push_str(&format!("{}{}\n", "  ".repeat(dirs.len()), name));
+        }
+        measured_size(&lines, config)
+    }
+
+    /// Note the directories of `file` as listed in the directory tree
+    fn add_listing(&mut self, file: &ProcessedFile, config: &YekConfig) {
+        if let Some(tree_dirs) = &mut self.tree_dirs {
+            let path = display_path(&file.rel_path, config);
+            let parts: Vec<&str> = path.split('/').collect();
+            for depth in 1..parts.len() {
+                tree_dirs.insert(parts[..depth].join("/"));
+            }
+        }
+    }
+
+    /// Size of the banner of the tier of `file`, if it may start one. In priority order
+    /// each tier's files are together, so only its first file has a banner; in another
+    /// order any of them might.
+    fn banner_size(&mut self, file: &ProcessedFile, config: &YekConfig) -> usize {
+        let Some(name) = self
+            .tiers
+            .as_deref()
+            .and_then(|tiers| priority_tier(file.priority, tiers))
+        else {
+            return 0;
+        };
+        let group = match self.chunk_number {
+            Some(_) => split_group(&file.rel_path),
+            None => String::new(),
+        };
+        let banner = format!("=== {} ===\n", name);
+        let first = self.banners.insert((group, name.to_string()));
+        if first || config.sort_by != "priority" {
+            measured_size(&banner, config)
+        } else {
+            0
+        }
+    }
+}
+
+/// Size of `text` towards the cap: its tokens in token mode, otherwise its bytes
+fn measured_size(text: &str, config: &YekConfig) -> usize {
+    if text.is_empty() {
+        0
+    } else if config.token_mode {
+        count_tokens_with(text, &config.tokenizer)
+    } else {
+        text.len()
+    }
+}
+
+/// The size or token cap of one chunk: max_size or tokens, and no more than max_total_size
+fn size_cap(config: &YekConfig) -> Result<usize> {
     let cap = if config.token_mode {
-        parse_token_limit(&config.tokens)?
+        parse_size_input(&config.tokens, true)?
     } else {
-        ByteSize::from_str(&config.max_size)
-            .map_err(|e| anyhow!("max_size: Invalid size format: {}", e))?
-            .as_u64() as usize
+        parse_size_input(&config.max_size, false)?
     };
+    match &config.max_total_size {
+        Some(total) => Ok(cap.min(parse_size_input(total, config.token_mode)?)),
+        None => Ok(cap),
+    }
+}
 
-    // Sort by priority (asc) and file_index (asc)
+/// Same as `select_files`, against the given `cap` in bytes or tokens and with the given
+/// `extras`
+fn select_files_under<'a>(
+    files: &'a [ProcessedFile],
+    mut extras: ChunkExtras,
+    cap: usize,
+    config: &YekConfig,
+) -> anyhow::Result<Vec<&'a ProcessedFile>> {
+    let mut accumulated = extras.reserved;
+
+    // Fill by priority (desc); files of equal priority are taken in path order
     let mut sorted_files: Vec<_> = files.iter().collect();
     sorted_files.sort_by(|a, b| {
-        a.priority
-            .cmp(&b.priority)
+        b.priority
+            .cmp(&a.priority)
             .then_with(|| a.rel_path.cmp(&b.rel_path))
     });
 
     let mut files_to_include = Vec::new();
+    let mut originals = HashMap::new();
     for file in sorted_files {
-        let content_size = if config.token_mode {
-            // Format the file content with template first, then count tokens
-            let formatted = if config.json {
-                serde_json::to_string(&serde_json::json!({
-                    "filename": &file.rel_path,
-                    "content": &file.content,
-                }))
-                .map_err(|e| anyhow!("Failed to serialize JSON: {}", e))?
-            } else {
-                config
-                    .output_template
-                    .replace("FILE_PATH", &file.rel_path)
-                    .replace("FILE_CONTENT", &file.content)
+        // A single document takes every file, whatever the cap
+        if config.single_document {
+            files_to_include.push(file);
+            continue;
+        }
+        let duplicate_of = originals.get(file.content.as_str()).copied();
+        let size = entry_size(file, files_to_include.is_empty(), duplicate_of, config)?
+            + extras.listing_size(file, config)
+            + extras.banner_size(file, config);
+        if accumulated + size > cap {
+            break;
+        }
+        accumulated += size;
+        extras.add_listing(file, config);
+        add_original(&mut originals, file, config);
+        files_to_include.push(file);
+    }
+    sort_for_output(&mut files_to_include, config);
+    Ok(files_to_include)
+}
+
+/// Pick the files of each group (see `split_by_directory`) that fit under the size or
+/// token cap of its chunk, from the highest priority down across all of them. Once a
+/// file doesn't fit, the lower-priority rest of its group is dropped. The first chunk
+/// lists the files of all of them in its table of contents and directory tree, so each
+/// file also needs room for its lines there, and once there is none left no more files
+/// are picked. Returns the kept files of each group in output order.
+fn select_split_files<'a>(
+    groups: &'a [(String, Vec<ProcessedFile>)],
+    config: &YekConfig,
+) -> Result<Vec<Vec<&'a ProcessedFile>>> {
+    let cap = size_cap(config)?;
+    let file_count = groups.iter().map(|(_, files)| files.len()).sum();
+    let last = groups.len().saturating_sub(1);
+    let mut extras = (0..groups.len())
+        .map(|index| ChunkExtras::new(index == 0, index == last, None, file_count, cap, config))
+        .collect::<Result<Vec<_>>>()?;
+    let mut accumulated: Vec<usize> = extras.iter().map(|extras| extras.reserved).collect();
+    let mut full = vec![false; groups.len()];
+    let mut selected = vec![Vec::new(); groups.len()];
+    let mut originals = vec![HashMap::new(); groups.len()];
+
+    let files = groups
+        .iter()
+        .enumerate()
+        .flat_map(|(index, (_, files))| files.iter().map(move |file| (index, file)));
+    // By priority (desc) across all groups, files of equal priority in path order
+    let mut sorted: Vec<_> = files.collect();
+    sorted.sort_by(|(_, a), (_, b)| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.rel_path.cmp(&b.rel_path))
+    });
+    for (index, file) in sorted {
+        if full[index] {
+            continue;
+        }
+        let duplicate_of = originals[index].get(file.content.as_str()).copied();
+        let size = entry_size(file, selected[index].is_empty(), duplicate_of, config)?
+            + extras[index].banner_size(file, config);
+        extras[0].chunk_number = Some(index + 1);
+        let listing = extras[0].listing_size(file, config);
+        if index == 0 {
+            if accumulated[0] + size + listing > cap {
+                full[0] = true;
+                continue;
+            }
+        } else if accumulated[0] + listing > cap {
+            // No room left for listing files in the first chunk
+            break;
+        } else if accumulated[index] + size > cap {
+            full[index] = true;
+            continue;
+        }
+        accumulated[index] += size;
+        accumulated[0] += listing;
+        extras[0].add_listing(file, config);
+        add_original(&mut originals[index], file, config);
+        selected[index].push(file);
+    }
+    for files_to_include in &mut selected {
+        sort_for_output(files_to_include, config);
+    }
+    Ok(selected)
+}
+
+/// With dedup, note `file` as the copy that keeps its content, unless an earlier
+/// (higher-priority) pick already has the same content. See `find_duplicates`.
+fn add_original<'a>(
+    originals: &mut HashMap<&'a str, &'a str>,
+    file: &'a ProcessedFile,
+    config: &YekConfig,
+) {
+    if config.dedup && !file.content.is_empty() {
+        originals
+            .entry(file.content.as_str())
+            .or_insert(file.rel_path.as_str());
+    }
+}
+
+/// Size of the entry of `file` towards the cap: the file as it's rendered, with its header
+/// (delimiter, path prefix, annotations) and what separates it from the previous one (see
+/// `entry_separator`) unless it's the `first` of its chunk. A duplicate of another file
+/// (see `find_duplicates`) is rendered without its content.
+fn entry_size(
+    file: &ProcessedFile,
+    first: bool,
+    duplicate_of: Option<&str>,
+    config: &YekConfig,
+) -> Result<usize> {
+    let content = match duplicate_of {
+        Some(_) => Cow::Borrowed(""),
+        None => file_content(file, config),
+    };
+    let path = display_path(&file.rel_path, config);
+    let formatted = if config.json || config.jsonl {
+        let mut entry = serde_json::json!({
+            "filename": &path,
+            "content": &content,
+        });
+        if let Some(original) = duplicate_of {
+            entry["duplicate_of"] = display_path(original, config).into();
+        }
+        serde_json::to_string(&entry).map_err(|e| anyhow!("Failed to serialize JSON: {}", e))?
+    } else {
+        render_file(
+            &path,
+            &content,
+            language_for_file(Path::new(&file.rel_path), &file.content),
+            file.commit.as_deref(),
+            duplicate_of
+                .map(|original| display_path(original, config))
+                .as_deref(),
+            config,
+        )
+    };
+    let separator = if first {
+        0
+    } else {
+        entry_separator(config).len()
+    };
+    Ok(if config.token_mode {
+        count_tokens_with(&formatted, &config.tokenizer)
+    } else {
+        formatted.len() + separator
+    })
+}
+
+/// Put picked files back in output order: priority (asc) by default, then path
+fn sort_for_output(files_to_include: &mut [&ProcessedFile], config: &YekConfig) {
+    match config.sort_by.as_str() {
+        "path" => files_to_include.sort_by(|a, b| a.rel_path.cmp(&b.rel_path)),
+        "size" => files_to_include.sort_by(|a, b| {
+            a.content
+                .len()
+                .cmp(&b.content.len())
+                .then_with(|| a.rel_path.cmp(&b.rel_path))
+        }),
+        _ => files_to_include.sort_by(|a, b| {
+            a.priority
+                .cmp(&b.priority)
+                .then_with(|| a.rel_path.cmp(&b.rel_path))
+        }),
+    }
+}
+
+/// What goes between two file entries in text, Markdown and XML output: a line break, or
+/// the file_separator on a line of its own
+fn entry_separator(config: &YekConfig) -> Cow<'static, str> {
+    match &config.file_separator {
+        Some(separator) => Cow::Owned(format!("\n{}\n", separator)),
+        None => Cow::Borrowed("\n"),
+    }
+}
+
+/// Contents of prompt_file as they start the output: verbatim, followed by a blank line.
+/// None without a prompt_file, and for paths-only and tree listings.
+fn prompt_block(config: &YekConfig) -> Result<Option<String>> {
+    let Some(path) = &config.prompt_file else {
+        return Ok(None);
+    };
+    if config.paths_only || config.tree {
+        return Ok(None);
+    }
+    let mut prompt = fs::read_to_string(path)
+        .map_err(|e| anyhow!("prompt_file: Failed to read {}: {}", path, e))?;
+    if !prompt.ends_with('\n') {
+        prompt.push('\n');
+    }
+    prompt.push('\n');
+    Ok(Some(prompt))
+}
+
+/// Sorted paths of the given files, one per line, or a JSON array of them with `json`.
+/// With `tree`, each directory is listed once and its contents are indented below it.
+fn paths_listing(files: &[&ProcessedFile], config: &YekConfig) -> Result<String> {
+    let mut paths: Vec<String> = files
+        .iter()
+        .map(|f| display_path(&f.rel_path, config))
+        .collect();
+    paths.sort();
+
+    if config.json {
+        return Ok(serde_json::to_string_pretty(&paths)?);
+    }
+    if !config.tree {
+        return Ok(paths.join("\n"));
+    }
+    Ok(tree_listing(&paths))
+}
+
+/// Sorted paths as an indented directory tree, each directory listed once as "name/"
+/// with its contents indented below it
+fn tree_listing(paths: &[String]) -> String {
+    // Sorted paths keep each directory's contents together, so only the directories
+    // not shared with the previous path need a line of their own
+    let mut lines = Vec::new();
+    let mut previous: Vec<&str> = Vec::new();
+    for path in paths {
+        let parts: Vec<&str> = path.split('/').collect();
+        let (name, dirs) = parts.split_last().unwrap_or((&"", &[]));
+        let shared = dirs
+            .iter()
+            .zip(&previous)
+            .take_while(|(a, b)| a == b)
+            .count();
+        for (depth, dir) in dirs.iter().enumerate().skip(shared) {
+            lines.push(format!("{}{}/", "  ".repeat(depth), dir));
+        }
+        lines.push(format!("{}{}", "  ".repeat(dirs.len()), name));
+        previous = dirs.to_vec();
+    }
+    lines.join("\n")
+}
+
+/// The configured priority tiers, or the default ones when none are configured
+fn priority_tiers(config: &YekConfig) -> Vec<PriorityTier> {
+    if !config.priority_tiers.is_empty() {
+        return config.priority_tiers.clone();
+    }
+    DEFAULT_PRIORITY_TIERS
+        .iter()
+        .map(|(name, min_priority)| PriorityTier {
+            name: name.to_string(),
+            min_priority: *min_priority,
+        })
+        .collect()
+}
+
+/// A path as shown in the output, with path_prefix in front, or made absolute with
+/// absolute_paths. Selection and priorities have already been worked out on the plain
+/// relative path.
+fn display_path(path: &str, config: &YekConfig) -> String {
+    if config.absolute_paths {
+        return absolute_path(path, config);
+    }
+    match &config.path_prefix {
+        Some(prefix) => format!("{}{}", prefix, path),
+        None => path.to_string(),
+    }
+}
+
+/// `path` joined onto the canonicalized directory it's relative to: the input directory,
+/// the one labelled by its first component with several of them (see `input_dir_labels`),
+/// or the current directory for files listed on stdin
+fn absolute_path(path: &str, config: &YekConfig) -> String {
+    let (dir, rest) = if config.from_stdin || config.input_dirs.is_empty() {
+        (".", path)
+    } else if config.input_dirs.len() == 1 {
+        (config.input_dirs[0].as_str(), path)
+    } else {
+        let labels = input_dir_labels(&config.input_dirs);
+        let found = path.split_once('/').and_then(|(label, rest)| {
+            let index = labels.iter().position(|l| l.as_deref() == Some(label))?;
+            Some((config.input_dirs[index].as_str(), rest))
+        });
+        match found {
+            Some(found) => found,
+            None => return path.to_string(),
+        }
+    };
+    let root = Path::new(dir)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(dir));
+    root.join(rest).to_string_lossy().to_string()
+}
+
+/// Write the files that fit under the size cap as JSON Lines: one object with filename,
+/// priority and content per line, flushed as it's written so readers can consume the
+/// records as they arrive. Returns the number of records written.
+pub fn write_jsonl(
+    files: &[ProcessedFile],
+    config: &YekConfig,
+    out: impl Write,
+) -> anyhow::Result<usize> {
+    let files_to_include = select_files(files, config)?;
+    write_jsonl_records(&files_to_include, config, out)?;
+    Ok(files_to_include.len())
+}
+
+fn write_jsonl_records(
+    files_to_include: &[&ProcessedFile],
+    config: &YekConfig,
+    mut out: impl Write,
+) -> anyhow::Result<()> {
+    let duplicates = if config.dedup {
+        find_duplicates(files_to_include)
+    } else {
+        HashMap::new()
+    };
+    for f in files_to_include {
+        let mut record = match duplicates.get(f.rel_path.as_str()) {
+            Some(original) => serde_json::json!({
+                "filename": display_path(&f.rel_path, config),
+                "priority": f.priority,
+                "content": "",
+                "duplicate_of": display_path(original, config),
+            }),
+            None => serde_json::json!({
+                "filename": display_path(&f.rel_path, config),
+                "priority": f.priority,
+                "content": file_content(f, config),
+            }),
+        };
+        if let Some(commit) = &f.commit {
+            record["commit"] = commit.as_str().into();
+        }
+        serde_json::to_writer(&mut out, &record)?;
+        out.write_all(b"\n")?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+pub fn concat_files(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Result<String> {
+    render_selected(&select_files(files, config)?, config)
+}
+
+/// Render files already picked by `select_files`, in the order given, with the prompt,
+/// header and footer
+fn render_selected(files_to_include: &[&ProcessedFile], config: &YekConfig) -> Result<String> {
+    let tree_files = config.tree_header.then_some(files_to_include);
+    let toc = config.toc.then(|| toc_entries(files_to_include, config));
+    let (content, offsets) =
+        render_selected_with_offsets(files_to_include, tree_files, toc.as_deref(), config)?;
+    let mut chunks = [Chunk {
+        index: 0,
+        group: None,
+        files: files_to_include
+            .iter()
+            .map(|f| f.rel_path.clone())
+            .collect(),
+        priorities: files_to_include.iter().map(|f| f.priority).collect(),
+        offsets,
+        content,
+    }];
+    frame_chunks(&mut chunks, prompt_block(config)?.as_deref(), config);
+    let [chunk] = chunks;
+    Ok(chunk.content)
+}
+
+/// Entries of the table of contents for the given files: their paths as shown in the output
+fn toc_entries(files: &[&ProcessedFile], config: &YekConfig) -> Vec<String> {
+    files
+        .iter()
+        .map(|f| display_path(&f.rel_path, config))
+        .collect()
+}
+
+/// Render files in the order given, without the prompt, header and footer (see
+/// `frame_chunks`), and return where each file's entry starts in the output (see
+/// `Chunk::offsets`). A directory tree of `tree_files` goes at the top when given, and
+/// a table of contents with the `toc` entries below it. With `toc` set but no entries
+/// given, JSON output keeps its `{"toc", "files"}` shape with an empty table of contents.
+fn render_selected_with_offsets(
+    files_to_include: &[&ProcessedFile],
+    tree_files: Option<&[&ProcessedFile]>,
+    toc: Option<&[String]>,
+    config: &YekConfig,
+) -> Result<(String, Option<Vec<usize>>)> {
+    if config.paths_only || config.tree {
+        return Ok((paths_listing(files_to_include, config)?, None));
+    }
+
+    if config.jsonl {
+        let mut out = Vec::new();
+        write_jsonl_records(files_to_include, config, &mut out)?;
+        let mut output = String::from_utf8(out)?;
+        output.pop();
+        return Ok((output, None));
+    }
+
+    let duplicates = if config.dedup {
+        find_duplicates(files_to_include)
+    } else {
+        HashMap::new()
+    };
+
+    let display = |path: &str| display_path(path, config);
+
+    if config.json {
+        // JSON array of objects
+        let entries = files_to_include
+            .iter()
+            .map(|f| {
+                let mut entry = match duplicates.get(f.rel_path.as_str()) {
+                    Some(original) => serde_json::json!({
+                        "filename": display(&f.rel_path),
+                        "content": "",
+                        "duplicate_of": display(original),
+                    }),
+                    None => serde_json::json!({
+                        "filename": display(&f.rel_path),
+                        "content": file_content(f, config),
+                    }),
+                };
+                if let Some(commit) = &f.commit {
+                    entry["commit"] = commit.as_str().into();
+                }
+                entry
+            })
+            .collect::<Vec<_>>();
+        let output = if config.toc {
+            serde_json::to_string_pretty(&serde_json::json!({
+                "toc": toc.unwrap_or_default(),
+                "files": entries,
+            }))?
+        } else {
+            serde_json::to_string_pretty(&entries)?
+        };
+        Ok((output, None))
+    } else {
+        // Wrap each file in the configured output format
+        let rendered = files_to_include
+            .iter()
+            .map(|f| match duplicates.get(f.rel_path.as_str()) {
+                Some(original) => render_file(
+                    &display(&f.rel_path),
+                    "",
+                    language_for_file(Path::new(&f.rel_path), &f.content),
+                    f.commit.as_deref(),
+                    Some(&display(original)),
+                    config,
+                ),
+                None => render_file(
+                    &display(&f.rel_path),
+                    &file_content(f, config),
+                    language_for_file(Path::new(&f.rel_path), &f.content),
+                    f.commit.as_deref(),
+                    None,
+                    config,
+                ),
+            })
+            .collect::<Vec<_>>();
+        // With tier_headers, a banner goes before the first file of each tier
+        let tiers = config.tier_headers.then(|| priority_tiers(config));
+        let separator = entry_separator(config);
+        let mut previous_tier = None;
+        let mut entries = Vec::with_capacity(rendered.len());
+        let mut offsets = Vec::with_capacity(rendered.len());
+        let mut offset = 0;
+        for (f, entry) in files_to_include.iter().zip(rendered) {
+            let tier = tiers
+                .as_deref()
+                .and_then(|tiers| priority_tier(f.priority, tiers));
+            let banner = match tier {
+                Some(name) if tier != previous_tier => format!("=== {} ===\n", name),
+                _ => String::new(),
             };
-            count_tokens(&formatted)
+            previous_tier = tier;
+            offsets.push(offset + banner.len());
+            offset += banner.len() + entry.len() + separator.len();
+            entries.push(banner + &entry);
+        }
+        let body = entries.join(&separator);
+        // Whatever goes before the entries shifts their offsets
+        let mut preamble_len = 0;
+        let mut output = if let Some(toc) = toc {
+            let entries: Vec<String> = toc.iter().map(|entry| format!("- {entry}")).collect();
+            let toc = format!("Table of contents:\n{}\n\n", entries.join("\n"));
+            preamble_len += toc.len();
+            toc + &body
         } else {
-            file.content.len()
+            body
         };
+        if let Some(tree_files) = tree_files {
+            let mut paths: Vec<String> = tree_files.iter().map(|f| display(&f.rel_path)).collect();
+            paths.sort();
+            let tree = format!("Directory tree:\n{}\n\n", tree_listing(&paths));
+            preamble_len += tree.len();
+            output = tree + &output;
+        }
 
-        if accumulated + content_size <= cap {
-            accumulated += content_size;
-            files_to_include.push(file);
-        } else {
-            break;
+        let offsets = offsets
+            .into_iter()
+            .map(|offset| offset + preamble_len)
+            .collect();
+        Ok((output, Some(offsets)))
+    }
+}
+
+/// The text that starts a file header line in `template`, e.g. ">>>>" for ">>>> FILE_PATH"
+fn template_delimiter(template: &str) -> Option<&str> {
+    let before_path = &template[..template.find("FILE_PATH")?];
+    let line_start = before_path.rfind('\n').map_or(0, |i| i + 1);
+    Some(before_path[line_start..].trim()).filter(|d| !d.is_empty())
+}
+
+/// Warning for files in `chunks` with lines starting with the header delimiter, since
+/// readers of the output can't tell those lines apart from real file headers. `files` are
+/// the collected files the chunks were rendered from. None when there are no such files,
+/// and for output other than text with per-file entries.
+pub fn delimiter_collision_warning(
+    chunks: &[Chunk],
+    files: &[ProcessedFile],
+    config: &YekConfig,
+) -> Option<String> {
+    if config.output_format != OutputFormat::Text || chunks.iter().any(|c| c.offsets.is_none()) {
+        return None;
+    }
+    let delimiter = template_delimiter(&config.output_template)?;
+    let included: HashSet<&str> = chunks
+        .iter()
+        .flat_map(|c| c.files.iter().map(String::as_str))
+        .collect();
+    let colliding: Vec<&str> = files
+        .iter()
+        .filter(|f| included.contains(f.rel_path.as_str()))
+        .filter(|f| f.content.lines().any(|line| line.starts_with(delimiter)))
+        .map(|f| f.rel_path.as_str())
+        .collect();
+    let first = colliding.first()?;
+    Some(format!(
+        "Warning: {} file(s) contain lines starting with the '{}' delimiter (e.g. {}). \
+         Use --output-template to pick a different delimiter.",
+        colliding.len(),
+        delimiter,
+        first
+    ))
+}
+
+/// Render one file in the configured output format. `language` is the file's language (see
+/// `language_for_file`). Duplicates (see `find_duplicates`) are rendered with no content
+/// and a reference to the file carrying it.
+fn render_file(
+    path: &str,
+    content: &str,
+    language: Option<&str>,
+    commit: Option<&str>,
+    duplicate_of: Option<&str>,
+    config: &YekConfig,
+) -> String {
+    match config.output_format {
+        OutputFormat::Text => {
+            let mut label = Cow::Borrowed(path);
+            if let Some(commit) = commit {
+                label = Cow::Owned(format!("{} @{}", label, commit));
+            }
+            if config.show_language {
+                if let Some(language) = language {
+                    label = Cow::Owned(format!("{} ({})", label, language));
+                }
+            }
+            // Stats of the content as it appears in the output; duplicates have none
+            if config.show_file_stats && duplicate_of.is_none() {
+                label = Cow::Owned(format!(
+                    "{} ({}, {} tokens)",
+                    label,
+                    ByteSize::b(content.len() as u64),
+                    count_tokens_with(content, &config.tokenizer)
+                ));
+            }
+            if let Some(original) = duplicate_of {
+                label = Cow::Owned(format!("{} (duplicate of {})", label, original));
+            }
+            config
+                .output_template
+                .replace("FILE_PATH", &label)
+                .replace("FILE_CONTENT", content)
+                // Handle both literal "\n" and escaped "\\n"
+                .replace("\\\\\n", "\n") // First handle escaped newline
+                .replace("\\\\n", "\n") // Then handle escaped \n sequence
         }
+        OutputFormat::Xml => {
+            let commit = commit
+                .map(|commit| format!(" commit=\"{}\"", escape_xml(commit)))
+                .unwrap_or_default();
+            match duplicate_of {
+                Some(original) => format!(
+                    "<file path=\"{}\"{} duplicate_of=\"{}\" />",
+                    escape_xml(path),
+                    commit,
+                    escape_xml(original)
+                ),
+                None => format!(
+                    "<file path=\"{}\"{}>\n{}\n</file>",
+                    escape_xml(path),
+                    commit,
+                    escape_xml(content.strip_suffix('\n').unwrap_or(content))
+                ),
+            }
+        }
+        OutputFormat::Markdown => {
+            let language = language.unwrap_or("text");
+            let path = match commit {
+                Some(commit) => Cow::Owned(format!("{} @{}", path, commit)),
+                None => Cow::Borrowed(path),
+            };
+            let info = match duplicate_of {
+                Some(original) => format!("{} {} (duplicate of {})", language, path, original),
+                None => format!("{} {}", language, path),
+            };
+            // The fence must be longer than any run of backticks inside the content
+            let longest_run = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+            let fence = "`".repeat(longest_run.max(2) + 1);
+            let content = content.strip_suffix('\n').unwrap_or(content);
+            if content.is_empty() {
+                format!("{fence}{info}\n{fence}")
+            } else {
+                format!("{fence}{info}\n{content}\n{fence}")
+            }
+        }
+    }
+}
+
+/// Escape text for use in XML content and attribute values.
+fn escape_xml(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"']) {
+        return Cow::Borrowed(text);
     }
+    Cow::Owned(
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;"),
+    )
+}
+
+/// Guess the language of a file from its name and content, as used for markdown code fences
+/// and `show_language`: by path (see `language_for_path`), or for a file without an
+/// extension, by the interpreter on its `#!` line.
+pub fn language_for_file(path: &Path, content: &str) -> Option<&'static str> {
+    language_for_path(path).or_else(|| {
+        file_extension(path)
+            .is_none()
+            .then(|| language_for_shebang(content))
+            .flatten()
+    })
+}
+
+/// Language of a script's `#!` interpreter, e.g. "bash" for "#!/bin/sh" and "python"
+/// for "#!/usr/bin/env python3"
+fn language_for_shebang(content: &str) -> Option<&'static str> {
+    let line = content.lines().next()?.strip_prefix("#!")?;
+    let mut words = line.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    // "#!/usr/bin/env -S deno run" names the interpreter after env and its flags
+    if interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-'))?;
+    }
+    // Versioned names like python3.12 or ruby2.7
+    let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let language = match name {
+        "sh" | "bash" | "dash" | "ksh" => "bash",
+        "zsh" => "zsh",
+        "fish" => "fish",
+        "python" | "pypy" => "python",
+        "node" | "nodejs" | "deno" | "bun" => "javascript",
+        "ruby" => "ruby",
+        "perl" => "perl",
+        "php" => "php",
+        "lua" => "lua",
+        "Rscript" => "r",
+        "pwsh" => "powershell",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// Guess the language of a file from its extension, or its name for files like
+/// `Dockerfile` and `.gitignore`. Returns None for unknown extensions.
+pub fn language_for_path(path: &Path) -> Option<&'static str> {
+    let name = path.file_name().and_then(|name| name.to_str());
+    match name {
+        Some("Dockerfile" | "Containerfile") => return Some("dockerfile"),
+        Some("Makefile" | "makefile" | "GNUmakefile") => return Some("makefile"),
+        Some("CMakeLists.txt") => return Some("cmake"),
+        Some("Gemfile" | "Rakefile") => return Some("ruby"),
+        Some("Jenkinsfile") => return Some("groovy"),
+        Some(".gitignore" | ".dockerignore" | ".yekignore") => return Some("gitignore"),
+        Some(".bashrc" | ".bash_profile" | ".profile") => return Some("bash"),
+        Some(".zshrc") => return Some("zsh"),
+        _ => {}
+    }
+    // e.g. Dockerfile.dev
+    if name.is_some_and(|name| name.starts_with("Dockerfile.")) {
+        return Some("dockerfile");
+    }
+    let language = match file_extension(path)? {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "scala" => "scala",
+        "c" | "h" => "c",
+        "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => "cpp",
+        "cs" => "csharp",
+        "swift" => "swift",
+        "rb" => "ruby",
+        "php" => "php",
+        "lua" => "lua",
+        "dart" => "dart",
+        "ex" | "exs" => "elixir",
+        "erl" => "erlang",
+        "hs" => "haskell",
+        "ml" | "mli" => "ocaml",
+        "r" | "R" => "r",
+        "sh" | "bash" => "bash",
+        "zsh" => "zsh",
+        "fish" => "fish",
+        "ps1" => "powershell",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "xml" => "xml",
+        "md" | "markdown" => "markdown",
+        "proto" => "protobuf",
+        "graphql" | "gql" => "graphql",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// Name of the directory being serialized: the first input directory, or the current
+/// directory when reading the file list from stdin.
+fn repo_name(config: &YekConfig) -> String {
+    let dir = match config.input_dirs.first() {
+        Some(dir) if !config.from_stdin && is_git_url(dir) => return repo_name_from_url(dir),
+        Some(dir) if !config.from_stdin => PathBuf::from(dir),
+        _ => PathBuf::from("."),
+    };
+    dir.canonicalize()
+        .ok()
+        .and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| dir.to_string_lossy().to_string())
+}
+
+/// Checksum used with `skip_if_unchanged`: the settings, the HEAD commit of each input
+/// directory's repository, and the path, size and modification time of every file the walk
+/// finds, so any change that could alter the output gives a new one. None when the files
+/// can't be known before serializing: remote repositories and file lists read from stdin.
+pub fn unchanged_checksum(config: &YekConfig) -> Result<Option<String>> {
+    if config.from_stdin || config.input_dirs.iter().any(|dir| is_git_url(dir)) {
+        return Ok(None);
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_string(config)?);
+    for dir in &config.input_dirs {
+        let base_dir = Path::new(dir);
+        if let Some(head) = get_head_commit_git2(base_dir) {
+            hasher.update(head);
+        }
+        for path in walk_files(base_dir, config)? {
+            hasher.update(path.to_string_lossy().as_bytes());
+            if let Some((modified, size)) = file_stamp(&path) {
+                hasher.update(modified.to_le_bytes());
+                hasher.update(size.to_le_bytes());
+            }
+        }
+    }
+    let hex = format!("{:x}", hasher.finalize());
+    Ok(Some(hex[..8].to_owned()))
+}
+
+/// Name of an output file: `output_name_template` (or the default name) with {checksum},
+/// {repo}, {date}, {ext} and {group} filled in. `group` is the split group, if splitting.
+pub fn output_file_name(config: &YekConfig, checksum: &str, group: Option<&str>) -> String {
+    let template = match (&config.output_name_template, group) {
+        (Some(template), _) => template.as_str(),
+        (None, Some(_)) => DEFAULT_SPLIT_OUTPUT_NAME_TEMPLATE,
+        (None, None) => DEFAULT_OUTPUT_NAME_TEMPLATE,
+    };
+    let mut name = template
+        .replace("{checksum}", checksum)
+        .replace(
+            "{ext}",
+            if config.jsonl {
+                "jsonl"
+            } else if config.json {
+                "json"
+            } else {
+                "txt"
+            },
+        )
+        .replace("{group}", group.unwrap_or_default());
+    if name.contains("{repo}") {
+        name = name.replace("{repo}", &repo_name(config));
+    }
+    if name.contains("{date}") {
+        let date = OffsetDateTime::now_utc().date();
+        name = name.replace(
+            "{date}",
+            &format!(
+                "{}-{:02}-{:02}",
+                date.year(),
+                u8::from(date.month()),
+                date.day()
+            ),
+        );
+    }
+    name
+}
+
+/// Find files whose content is identical to another included file. Maps the path of each
+/// duplicate to the path carrying the content, which is the highest-priority copy (the
+/// first by path among equals), whatever the output order. Empty files are never treated
+/// as duplicates.
+fn find_duplicates<'a>(files: &[&'a ProcessedFile]) -> HashMap<&'a str, &'a str> {
+    // Go through the files the way they're picked, so the first copy seen is kept
+    let mut sorted = files.to_vec();
+    sorted.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.rel_path.cmp(&b.rel_path))
+    });
+    let mut originals: HashMap<&str, &str> = HashMap::new();
+    for f in sorted.into_iter().filter(|f| !f.content.is_empty()) {
+        originals
+            .entry(f.content.as_str())
+            .or_insert(f.rel_path.as_str());
+    }
+
+    files
+        .iter()
+        .filter_map(|f| {
+            let original = *originals.get(f.content.as_str())?;
+            (original != f.rel_path).then_some((f.rel_path.as_str(), original))
+        })
+        .collect()
+}
+
+/// Group files by the first component of their relative path, keeping their order
+/// within each group. Files at the top level go into a group named `_root`.
+/// Group names end up in output file names, so they are made safe for any file system:
+/// drive-letter colons are dropped (`C:/src/a.rs` goes into `C`) and other characters
+/// that aren't allowed in file names become `_`.
+pub fn split_by_directory(files: &[ProcessedFile]) -> Vec<(String, Vec<ProcessedFile>)> {
+    let mut groups: BTreeMap<String, Vec<ProcessedFile>> = BTreeMap::new();
+    for file in files {
+        groups
+            .entry(split_group(&file.rel_path))
+            .or_default()
+            .push(file.clone());
+    }
+    groups.into_iter().collect()
+}
+
+/// Group of a file with split_by: its top-level directory, or "_root" for files at the top
+fn split_group(rel_path: &str) -> String {
+    // Absolute paths (e.g. from a stdin file list) start with an empty component
+    let mut components = rel_path.split('/').filter(|c| !c.is_empty());
+    match (components.next(), components.next()) {
+        (Some(dir), Some(_)) => file_name_safe(dir),
+        _ => "_root".to_string(),
+    }
+}
+
+fn file_name_safe(name: &str) -> String {
+    let safe: String = name
+        .trim_end_matches(':')
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if safe.is_empty() {
+        "_root".to_string()
+    } else {
+        safe
+    }
+}
+
+/// Summarize what a run would include without producing the output: relative path,
+/// size in bytes and priority of each file, as a table or as JSON when `json` is set.
+pub fn dry_run_summary(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Result<String> {
+    let files_to_include = select_files(files, config)?;
 
     if config.json {
-        // JSON array of objects
-        Ok(serde_json::to_string_pretty(
+        return Ok(serde_json::to_string_pretty(
             &files_to_include
                 .iter()
                 .map(|f| {
                     serde_json::json!({
                         "filename": &f.rel_path,
-                        "content": &f.content,
+                        "size": f.content.len(),
+                        "priority": f.priority,
                     })
                 })
                 .collect::<Vec<_>>(),
-        )?)
-    } else {
-        // Use the user-defined template
-        Ok(files_to_include
+        )?);
+    }
+
+    let mut lines = vec![format!("{:>8}  {:>10}  {}", "PRIORITY", "BYTES", "PATH")];
+    for f in &files_to_include {
+        lines.push(format!(
+            "{:>8}  {:>10}  {}",
+            f.priority,
+            f.content.len(),
+            f.rel_path
+        ));
+    }
+    let total_bytes: usize = files_to_include.iter().map(|f| f.content.len()).sum();
+    lines.push(format!(
+        "{} files, {} bytes",
+        files_to_include.len(),
+        total_bytes
+    ));
+    Ok(lines.join("\n"))
+}
+
+/// For each file that would be included, the priority rule with the highest score (or the
+/// extension that scored it), that score, the Git boost and the final priority. A table,
+/// or JSON when `json` is set.
+pub fn explain_summary(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Result<String> {
+    let files_to_include = select_files(files, config)?;
+    let rules = with_default_syntax(&config.priority_rules, &config.priority_rule_syntax);
+    let explained: Vec<_> = files_to_include
+        .iter()
+        .map(|f| {
+            // Priorities were worked out on the path within its input directory
+            let path = input_relative_path(&f.rel_path, config);
+            let explanation = explain_file_priority(
+                path,
+                &rules,
+                &config.extension_priorities,
+                &config.priority_combine,
+            );
+            // Whatever the rules and depth don't account for came from the Git (or mtime) boost
+            let boost =
+                f.priority - explanation.priority - depth_priority(path, config.depth_priority);
+            (f, explanation, boost)
+        })
+        .collect();
+
+    if config.json {
+        return Ok(serde_json::to_string_pretty(
+            &explained
+                .iter()
+                .map(|(f, explanation, boost)| {
+                    serde_json::json!({
+                        "filename": &f.rel_path,
+                        "rule": explanation.rule,
+                        "rule_score": explanation.rule_score,
+                        "boost": boost,
+                        "priority": f.priority,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        )?);
+    }
+
+    let mut lines = vec![format!(
+        "{:>8}  {:>6}  {:>6}  {}  {}",
+        "PRIORITY", "SCORE", "BOOST", "PATH", "RULE"
+    )];
+    for (f, explanation, boost) in &explained {
+        lines.push(format!(
+            "{:>8}  {:>6}  {:>6}  {}  {}",
+            f.priority,
+            explanation.rule_score,
+            boost,
+            f.rel_path,
+            explanation.rule.as_deref().unwrap_or("-")
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Token and byte count of each file's content as it would appear in the output, largest
+/// first, followed by the totals. A table, or JSON when `json` is set. The size cap is not
+/// applied so the totals show the full footprint.
+pub fn count_summary(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Result<String> {
+    let mut counts: Vec<(&str, usize, usize)> = files
+        .par_iter()
+        .map(|f| {
+            let content = file_content(f, config);
+            (
+                f.rel_path.as_str(),
+                count_tokens_with(&content, &config.tokenizer),
+                content.len(),
+            )
+        })
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let total_tokens: usize = counts.iter().map(|(_, tokens, _)| tokens).sum();
+    let total_bytes: usize = counts.iter().map(|(_, _, bytes)| bytes).sum();
+
+    if config.json {
+        return Ok(serde_json::to_string_pretty(&serde_json::json!({
+            "files": counts
+                .iter()
+                .map(|(path, tokens, bytes)| {
+                    serde_json::json!({ "filename": path, "tokens": tokens, "bytes": bytes })
+                })
+                .collect::<Vec<_>>(),
+            "total_tokens": total_tokens,
+            "total_bytes": total_bytes,
+        }))?);
+    }
+
+    let mut lines = vec![format!("{:>10}  {:>10}  {}", "TOKENS", "BYTES", "PATH")];
+    for (path, tokens, bytes) in &counts {
+        lines.push(format!("{:>10}  {:>10}  {}", tokens, bytes, path));
+    }
+    lines.push(format!(
+        "{} files, {} tokens, {} bytes",
+        counts.len(),
+        total_tokens,
+        total_bytes
+    ));
+    Ok(lines.join("\n"))
+}
+
+/// Report of the files skipped as binary, grouped by reason, as printed by `--list-binary`
+pub fn binary_files_report(files: &[(String, BinaryReason)]) -> String {
+    let mut lines = vec![format!("{} files skipped as binary", files.len())];
+    for (reason, heading) in [
+        (BinaryReason::Extension, "Binary extension:"),
+        (
+            BinaryReason::Content,
+            "Binary content (null bytes or a binary signature):",
+        ),
+    ] {
+        let paths: Vec<&str> = files
             .iter()
-            .map(|f| {
-                config
-                    .output_template
-                    .replace("FILE_PATH", &f.rel_path)
-                    .replace("FILE_CONTENT", &f.content)
-                    // Handle both literal "\n" and escaped "\\n"
-                    .replace("\\\\\n", "\n") // First handle escaped newline
-                    .replace("\\\\n", "\n") // Then handle escaped \n sequence
+            .filter(|(_, r)| *r == reason)
+            .map(|(path, _)| path.as_str())
+            .collect();
+        if !paths.is_empty() {
+            lines.push(heading.to_string());
+            lines.extend(paths.iter().map(|path| format!("  {}", path)));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Contents of yek-manifest.json for a run that wrote `chunks` to the files at `outputs`:
+/// the output paths, the checksum, their total bytes and tokens, and each included file
+/// with its priority and the index of the chunk it's in. `files` are the collected files
+/// the chunks were rendered from.
+pub fn manifest_json(
+    checksum: &str,
+    outputs: &[String],
+    chunks: &[Chunk],
+    files: &[ProcessedFile],
+    config: &YekConfig,
+) -> anyhow::Result<String> {
+    let priorities: HashMap<&str, i32> = files
+        .iter()
+        .map(|f| (f.rel_path.as_str(), f.priority))
+        .collect();
+    let included = chunks
+        .iter()
+        .flat_map(|chunk| {
+            chunk.files.iter().map(|path| {
+                serde_json::json!({
+                    "path": path,
+                    "priority": priorities.get(path.as_str()).copied().unwrap_or_default(),
+                    "chunk": chunk.index,
+                })
             })
-            .collect::<Vec<_>>()
-            .join("\n"))
+        })
+        .collect::<Vec<_>>();
+
+    let manifest = serde_json::json!({
+        "checksum": checksum,
+        "outputs": outputs,
+        "total_bytes": chunks.iter().map(|c| c.content.len()).sum::<usize>(),
+        "total_tokens": chunks
+            .iter()
+            .map(|c| count_tokens_with(&c.content, &config.tokenizer))
+            .sum::<usize>(),
+        "files": included,
+    });
+    Ok(serde_json::to_string_pretty(&manifest)?)
+}
+
+/// Copy text to the system clipboard. Fails on systems without a clipboard,
+/// e.g. headless Linux without an X11 or Wayland session.
+pub fn copy_to_clipboard(text: &str) -> anyhow::Result<()> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| anyhow!("clipboard: unavailable: {}", e))?;
+    clipboard
+        .set_text(text)
+        .map_err(|e| anyhow!("clipboard: failed to copy: {}", e))
+}
+
+/// Parse a `since` threshold into a Unix timestamp in seconds. Accepts a Unix timestamp
+/// ("1700000000"), a date ("2024-01-31", midnight UTC) or an RFC 3339 date-time
+/// ("2024-01-31T12:00:00+02:00").
+pub fn parse_since(input: &str) -> anyhow::Result<u64> {
+    let input = input.trim();
+    if let Ok(timestamp) = input.parse::<u64>() {
+        return Ok(timestamp);
     }
+
+    let datetime = OffsetDateTime::parse(input, &Rfc3339)
+        .or_else(|_| {
+            Date::parse(input, format_description!("[year]-[month]-[day]"))
+                .map(|date| date.midnight().assume_utc())
+        })
+        .map_err(|_| {
+            anyhow!(
+                "since: Invalid date '{}'. Expected a Unix timestamp, YYYY-MM-DD or RFC 3339 date-time",
+                input
+            )
+        })?;
+    u64::try_from(datetime.unix_timestamp())
+        .map_err(|_| anyhow!("since: '{}' is before 1970-01-01", input))
 }
 
-/// Parse a token limit string like "800k" or "1000" into a number
-pub fn parse_token_limit(limit: &str) -> anyhow::Result<usize> {
-    if limit.to_lowercase().ends_with('k') {
-        limit[..limit.len() - 1]
-            .trim()
-            .parse::<usize>()
-            .map(|n| n * 1000)
-            .map_err(|e| anyhow!("tokens: Invalid token size: {}", e))
+/// Compress serialized output with one of the `SUPPORTED_COMPRESSIONS` formats.
+pub fn compress_output(data: &[u8], compress: &str) -> anyhow::Result<Vec<u8>> {
+    match compress {
+        "gzip" => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+        _ => Err(anyhow!("compress: Unknown compression '{}'", compress)),
+    }
+}
+
+/// Parse a size limit. In token mode this is a token count like "1000", "128k" or "1M".
+/// Otherwise it is a byte size like "10MB", "1.5GB", "2TB", "512K" or "64KiB". Units are
+/// binary whichever way they're written: "K", "KB" and "KiB" all mean 1024 bytes.
+pub fn parse_size_input(input: &str, token_mode: bool) -> anyhow::Result<usize> {
+    // Percentages are resolved against a model's context window by `apply_model_preset`
+    if input.trim().ends_with('%') {
+        return Err(anyhow!(
+            "{}: '{}' is a percentage of a model's context window, so it needs --model",
+            if token_mode { "tokens" } else { "max_size" },
+            input.trim()
+        ));
+    }
+    if token_mode {
+        parse_token_limit(input)
     } else {
-        limit
-            .parse::<usize>()
-            .map_err(|e| anyhow!("tokens: Invalid token size: {}", e))
+        parse_byte_size(input).map_err(|e| anyhow!("max_size: Invalid size format: {}", e))
     }
 }
 
-/// Count tokens using tiktoken's GPT-3.5-Turbo tokenizer for accuracy
+/// Parse a byte size like "10MB" or "1.5G" into bytes, rounding fractions to the nearest byte
+fn parse_byte_size(input: &str) -> anyhow::Result<usize> {
+    let input = input.trim();
+    let unit_start = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(unit_start);
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| anyhow!("'{}' doesn't start with a number", input))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(anyhow!("unknown unit '{}'", unit.trim())),
+    };
+    let bytes = (number * multiplier as f64).round();
+    if bytes >= usize::MAX as f64 {
+        return Err(anyhow!("'{}' is too large", input));
+    }
+    Ok(bytes as usize)
+}
+
+/// Parse a token limit string like "800k", "1M" or "1000" into a number
+pub fn parse_token_limit(limit: &str) -> anyhow::Result<usize> {
+    let lower = limit.trim().to_lowercase();
+    let (number, multiplier) = if let Some(number) = lower.strip_suffix('k') {
+        (number, 1_000)
+    } else if let Some(number) = lower.strip_suffix('m') {
+        (number, 1_000_000)
+    } else {
+        (lower.as_str(), 1)
+    };
+    let number = number
+        .trim()
+        .parse::<usize>()
+        .map_err(|e| anyhow!("tokens: Invalid token size: {}", e))?;
+    number.checked_mul(multiplier).ok_or_else(|| {
+        anyhow!(
+            "tokens: Invalid token size: '{}' is too large",
+            limit.trim()
+        )
+    })
+}
+
+/// Parse a percentage like "80%" and apply it to `whole`, rounding down
+pub fn parse_percentage_of(input: &str, whole: usize) -> anyhow::Result<usize> {
+    let percentage = input
+        .trim()
+        .strip_suffix('%')
+        .and_then(|number| number.trim().parse::<f64>().ok())
+        .filter(|p| *p > 0.0 && *p <= 100.0)
+        .ok_or_else(|| {
+            anyhow!(
+                "Invalid percentage '{}'. Expected a number above 0 and up to 100, e.g. \"80%\"",
+                input.trim()
+            )
+        })?;
+    Ok((whole as f64 * percentage / 100.0) as usize)
+}
+
+/// Count tokens using tiktoken's cl100k_base encoding (GPT-3.5/GPT-4) for accuracy
 pub fn count_tokens(text: &str) -> usize {
-    get_tokenizer().encode_with_special_tokens(text).len()
+    count_tokens_with(text, DEFAULT_TOKENIZER)
+}
+
+/// Count tokens with a named tokenizer (see `SUPPORTED_TOKENIZERS`).
+/// "whitespace" counts whitespace-separated words, a cheap but rough estimate.
+/// Unknown names fall back to the default BPE encoding.
+pub fn count_tokens_with(text: &str, tokenizer: &str) -> usize {
+    if tokenizer == "whitespace" {
+        return text.split_whitespace().count();
+    }
+    get_tokenizer(tokenizer)
+        .or_else(|| get_tokenizer(DEFAULT_TOKENIZER))
+        .expect("default tokenizer is always available")
+        .encode_with_special_tokens(text)
+        .len()
 }
@@ -1,10 +1,18 @@
 use anyhow::Result;
 use bytesize::ByteSize;
 use rayon::join;
-use std::path::Path;
+use std::{
+    io::{self, Write},
+    path::Path,
+};
 use tracing::{debug, Level};
 use tracing_subscriber::fmt;
-use yek::{config::YekConfig, serialize_repo};
+use yek::{
+    binary_files_report, chunk_stats, collect_files_with_errors, compress_output,
+    config::YekConfig, copy_to_clipboard, delimiter_collision_warning, manifest_json,
+    output_file_name, parallel::ProcessedFile, render_chunks, unchanged_checksum,
+    watch::watch_input_dirs, write_chunks, write_jsonl, Chunk, CollectedFiles, SerializeStats,
+};
 
 fn main() -> Result<()> {
     // 1) Parse CLI + config files:
@@ -38,55 +46,264 @@ fn main() -> Result<()> {
         debug!("Configuration:\n{}", config_str);
     }
 
-    // If streaming => skip checksum + read. Just do single-thread call to serialize_repo.
+    if full_config.watch {
+        let watched = full_config.clone();
+        watch_input_dirs(&watched, || run(&mut full_config))?;
+    } else {
+        run(&mut full_config)?;
+    }
+
+    Ok(())
+}
+
+/// Serialize the input directories and write the output where the config asks for it
+fn run(full_config: &mut YekConfig) -> Result<()> {
+    // A dry-run, count-only or explain summary is meant to be read, so it is never compressed
+    let summary_only = full_config.dry_run || full_config.count_only || full_config.explain;
+    let compress = if summary_only {
+        None
+    } else {
+        full_config.compress.clone()
+    };
+
+    // If copying to the clipboard => no output file and nothing on stdout unless copying fails.
+    // If streaming => skip checksum + read. Just render the chunks and print them.
     // If not streaming => run checksum + repo serialization in parallel.
-    if full_config.stream {
-        let (output, files) = serialize_repo(&full_config)?;
-        // We print actual text to stdout:
-        println!("{}", output);
+    if full_config.clipboard && !summary_only {
+        let collected = collect(full_config)?;
+        let chunks = render_chunks(&collected.files, full_config)?;
+        warn_dropped(collected.files.len() - files_in(&chunks), full_config);
+        warn_collisions(&chunks, &collected.files, full_config);
+        let output = chunks
+            .into_iter()
+            .map(|chunk| chunk.content)
+            .collect::<Vec<_>>()
+            .join("\n");
+        match copy_to_clipboard(&output) {
+            Ok(()) => eprintln!(
+                "Copied {} to the clipboard",
+                ByteSize::b(output.len() as u64)
+            ),
+            Err(e) => {
+                eprintln!("Warning: {}. Printing to stdout instead.", e);
+                println!("{}", output);
+            }
+        }
+    } else if full_config.stream && full_config.jsonl && !summary_only && compress.is_none() {
+        // JSON Lines go out record by record rather than as one big string
+        let collected = collect(full_config)?;
+        let files = &collected.files;
+        let written = write_jsonl(files, full_config, io::stdout().lock())?;
+        warn_dropped(files.len() - written, full_config);
 
         if full_config.debug {
-            debug!("{} files processed (streaming).", files.len());
-            debug!("Output lines: {}", output.lines().count());
+            let chunks = render_chunks(files, full_config)?;
+            log_stats(
+                &SerializeStats {
+                    unreadable_files: collected.read_errors.len(),
+                    redactions: collected.redactions,
+                    ..chunk_stats(&chunks, files, full_config)
+                },
+                full_config,
+            );
+        }
+    } else if full_config.stream {
+        let collected = collect(full_config)?;
+        let files = &collected.files;
+        let chunks = render_chunks(files, full_config)?;
+        warn_dropped(files.len() - files_in(&chunks), full_config);
+        warn_collisions(&chunks, files, full_config);
+        for chunk in &chunks {
+            // We print actual text to stdout, or its compressed bytes:
+            if let Some(compress) = &compress {
+                let compressed =
+                    compress_output(format!("{}\n", chunk.content).as_bytes(), compress)?;
+                io::stdout().lock().write_all(&compressed)?;
+            } else {
+                println!("{}", chunk.content);
+            }
         }
-    } else {
-        // Not streaming => run repo serialization & checksum in parallel
-        let (serialization_res, checksum_res) = join(
-            || serialize_repo(&full_config),
-            || YekConfig::get_checksum(&full_config.input_dirs),
-        );
-
-        // Handle both results
-        let (output_string, files) = serialization_res?;
-        let checksum = checksum_res;
 
-        // Now set the final output file with the computed checksum
-        let extension = if full_config.json { "json" } else { "txt" };
+        if full_config.debug {
+            log_stats(
+                &SerializeStats {
+                    unreadable_files: collected.read_errors.len(),
+                    redactions: collected.redactions,
+                    ..chunk_stats(&chunks, files, full_config)
+                },
+                full_config,
+            );
+        }
+    } else {
         let output_dir = full_config.output_dir.as_ref().ok_or_else(|| {
             anyhow::anyhow!("Output directory is required when not in streaming mode. This may indicate a configuration validation error.")
         })?;
+        let suffix = if compress.is_some() { ".gz" } else { "" };
 
-        let final_path = Path::new(output_dir)
-            .join(format!("yek-output-{}.{}", checksum, extension))
-            .to_string_lossy()
-            .to_string();
-        full_config.output_file_full_path = Some(final_path.clone());
+        // The output for this checksum is already there if the inputs haven't changed
+        let unchanged = if full_config.skip_if_unchanged {
+            unchanged_checksum(full_config)?
+        } else {
+            None
+        };
+        if let Some(checksum) = &unchanged {
+            let file_name = output_file_name(full_config, checksum, None);
+            let existing = Path::new(output_dir)
+                .join(format!("{}{}", file_name, suffix))
+                .to_string_lossy()
+                .to_string();
+            if Path::new(&existing).is_file() {
+                debug!("Inputs unchanged, keeping {}", existing);
+                println!("{}", existing);
+                full_config.output_file_full_path = Some(existing);
+                return Ok(());
+            }
+        }
 
-        // If debug, show stats
-        if full_config.debug {
-            let size = ByteSize::b(output_string.len() as u64);
-            debug!("{} files processed", files.len());
-            debug!("{} generated", size);
-            debug!("{} lines generated", output_string.lines().count());
+        // Not streaming => run repo serialization & checksum in parallel
+        let (files_res, checksum) = join(
+            || collect(full_config),
+            || unchanged.unwrap_or_else(|| YekConfig::get_checksum(&full_config.input_dirs)),
+        );
+        let collected = files_res?;
+        let files = &collected.files;
+
+        // One chunk per top-level directory when splitting, otherwise a single one
+        let chunks = render_chunks(files, full_config)?;
+        warn_dropped(files.len() - files_in(&chunks), full_config);
+        warn_collisions(&chunks, files, full_config);
+
+        // Stats for the debug log, or else for the summary line unless quiet
+        let stats = (full_config.debug || !full_config.quiet).then(|| SerializeStats {
+            unreadable_files: collected.read_errors.len(),
+            redactions: collected.redactions,
+            ..chunk_stats(&chunks, files, full_config)
+        });
+        if let (true, Some(stats)) = (full_config.debug, &stats) {
+            log_stats(stats, full_config);
         }
 
-        // Actually write the final output file.
-        // We'll do it right here (instead of inside `serialize_repo`) to ensure we use our new final_path:
-        std::fs::write(&final_path, output_string.as_bytes())?;
+        // Write the output files, named with the computed checksum, and print their paths
+        let written = write_chunks(&chunks, Path::new(output_dir), &checksum, full_config)?;
+        for path in &written {
+            println!("{}", path);
+        }
+        full_config.output_file_full_path = written.last().cloned();
+
+        if full_config.manifest {
+            let manifest = manifest_json(&checksum, &written, &chunks, files, full_config)?;
+            std::fs::write(Path::new(output_dir).join("yek-manifest.json"), manifest)?;
+        }
 
-        // Print path to stdout (like original code did)
-        println!("{}", final_path);
+        if let (false, Some(stats)) = (full_config.debug, &stats) {
+            let destination = match written.as_slice() {
+                [path] => path.clone(),
+                paths => format!("{} files in {}", paths.len(), output_dir),
+            };
+            eprintln!(
+                "{}",
+                summary_line(stats, &destination, full_config.token_mode)
+            );
+        }
     }
 
     Ok(())
 }
+
+/// Collect the input files, listing the binary ones with list_binary, and telling where
+/// remote clones were kept unless quiet
+fn collect(config: &YekConfig) -> Result<CollectedFiles> {
+    let collected = collect_files_with_errors(config)?;
+    if config.list_binary {
+        eprintln!("{}", binary_files_report(&collected.binary_files));
+    }
+    if !config.quiet {
+        for (url, path) in &collected.kept_clones {
+            eprintln!("Kept clone of {} at {}", url, path);
+        }
+    }
+    Ok(collected)
+}
+
+/// Number of files in the chunks
+fn files_in(chunks: &[Chunk]) -> usize {
+    chunks.iter().map(|chunk| chunk.files.len()).sum()
+}
+
+/// Warn about files whose lines look like file headers (see `delimiter_collision_warning`),
+/// unless quiet
+fn warn_collisions(chunks: &[Chunk], files: &[ProcessedFile], config: &YekConfig) {
+    if config.quiet {
+        return;
+    }
+    if let Some(warning) = delimiter_collision_warning(chunks, files, config) {
+        eprintln!("{}", warning);
+    }
+}
+
+/// Warn that the size cap left `dropped` files out, unless quiet
+fn warn_dropped(dropped: usize, config: &YekConfig) {
+    if dropped > 0 && !config.quiet {
+        let cap = if config.token_mode {
+            "tokens"
+        } else {
+            "max_size"
+        };
+        let limits = match config.max_total_size {
+            Some(_) => format!("{} and max_total_size limits", cap),
+            None => format!("{} limit", cap),
+        };
+        eprintln!(
+            "Warning: Dropped {} lower-priority file(s) to stay within the {}",
+            dropped, limits
+        );
+    }
+}
+
+fn log_stats(stats: &SerializeStats, config: &YekConfig) {
+    debug!(
+        "{} files in the output, {} left out by the size cap, {} unreadable, {} secrets redacted",
+        stats.total_files, stats.skipped_files, stats.unreadable_files, stats.redactions
+    );
+    if config.token_mode {
+        debug!(
+            "{} generated, {} tokens",
+            ByteSize::b(stats.total_bytes as u64),
+            stats.total_tokens
+        );
+    } else {
+        debug!("{} generated", ByteSize::b(stats.total_bytes as u64));
+    }
+}
+
+/// One-line summary of a run, e.g. "yek: 142 files, 3.1 MB → out.txt", with the token
+/// count after the size in token mode ("yek: 142 files, 3.1 MB, 812.4k tokens → out.txt"),
+/// e.g. " (2 unreadable files skipped)" at the end when some files couldn't be read
+/// and " (3 secrets redacted)" when redact masked some
+fn summary_line(stats: &SerializeStats, destination: &str, token_mode: bool) -> String {
+    let tokens = match stats.total_tokens {
+        _ if !token_mode => String::new(),
+        n if n < 1_000 => format!(", {} tokens", n),
+        n if n < 1_000_000 => format!(", {:.1}k tokens", n as f64 / 1_000.0),
+        n => format!(", {:.1}M tokens", n as f64 / 1_000_000.0),
+    };
+    let unreadable = match stats.unreadable_files {
+        0 => String::new(),
+        1 => " (1 unreadable file skipped)".to_string(),
+        n => format!(" ({} unreadable files skipped)", n),
+    };
+    let redacted = match stats.redactions {
+        0 => String::new(),
+        1 => " (1 secret redacted)".to_string(),
+        n => format!(" ({} secrets redacted)", n),
+    };
+    format!(
+        "yek: {} files, {}{} → {}{}{}",
+        stats.total_files,
+        ByteSize::b(stats.total_bytes as u64),
+        tokens,
+        destination,
+        unreadable,
+        redacted
+    )
+}
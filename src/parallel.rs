@@ -1,13 +1,27 @@
-use crate::{config::YekConfig, priority::get_file_priority, Result};
-use content_inspector::{inspect, ContentType};
-use ignore::gitignore::GitignoreBuilder;
-use path_slash::PathBufExt;
+use crate::{
+    cache::FileCache,
+    config::YekConfig,
+    decode_text,
+    defaults::{DEFAULT_BINARY_SCAN_BYTES, DEFAULT_NULL_BYTE_TOLERANCE},
+    is_binary_content, parse_size_input,
+    priority::{depth_priority, get_file_priority_with_extensions, with_default_syntax},
+    Result,
+};
+use anyhow::anyhow;
+use bytesize::ByteSize;
+use encoding_rs::Encoding;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use indicatif::ProgressBar;
+use path_slash::PathExt;
 use rayon::prelude::*;
 use std::{
     collections::HashMap,
-    fs,
-    path::Path,
-    sync::{mpsc, Arc},
+    fs, io,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
 };
 use tracing::debug;
 
@@ -17,85 +31,112 @@ pub struct ProcessedFile {
     pub file_index: usize,
     pub rel_path: String,
     pub content: String,
+    /// Short hash of the last commit that touched the file, filled in with show_commit
+    pub commit: Option<String>,
+}
+
+/// Why a file was skipped as binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BinaryReason {
+    /// Binary content, in a file with one of the configured binary extensions
+    Extension,
+    /// Binary content (null bytes or a known binary signature) in a file whose
+    /// extension doesn't give it away
+    Content,
+}
+
+/// Collects the files skipped as binary, with the reason
+#[derive(Default)]
+pub struct BinaryFiles(Mutex<Vec<(String, BinaryReason)>>);
+
+impl BinaryFiles {
+    pub fn record(&self, rel_path: String, reason: BinaryReason) {
+        self.0.lock().unwrap().push((rel_path, reason));
+    }
+
+    /// The recorded files, sorted by reason, then path
+    pub fn into_sorted(self) -> Vec<(String, BinaryReason)> {
+        let mut files = self.0.into_inner().unwrap();
+        files.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        files
+    }
+}
+
+/// A file that couldn't be read: its relative path and the error message
+pub type ReadError = (String, String);
+
+/// Collects the files that couldn't be read, with the error
+#[derive(Default)]
+pub struct ReadErrors(Mutex<Vec<ReadError>>);
+
+impl ReadErrors {
+    pub fn record(&self, rel_path: String, error: String) {
+        self.0.lock().unwrap().push((rel_path, error));
+    }
+
+    /// The recorded files and their errors, sorted by path
+    pub fn into_sorted(self) -> Vec<ReadError> {
+        let mut errors = self.0.into_inner().unwrap();
+        errors.sort();
+        errors
+    }
+
+    /// The recorded error with the lowest path, if any
+    fn first(&self) -> Option<ReadError> {
+        self.0.lock().unwrap().iter().min().cloned()
+    }
 }
 
 /// Walk files in parallel, skipping ignored paths, then read each file's contents
-/// in a separate thread. Return the resulting `ProcessedFile` objects.
+/// and compute its priority in parallel. Return the resulting `ProcessedFile` objects.
 pub fn process_files_parallel(
     base_dir: &Path,
     config: &YekConfig,
     boost_map: &HashMap<String, i32>,
 ) -> Result<Vec<ProcessedFile>> {
-    let mut walk_builder = ignore::WalkBuilder::new(base_dir);
+    process_files_parallel_with(base_dir, config, boost_map, None, None, None, None)
+}
 
-    // Standard filters + no follow symlinks
-    walk_builder
-        .follow_links(false)
-        .standard_filters(true)
-        .require_git(false);
+/// Same as `process_files_parallel`, but unchanged files are taken from `cache`
+/// instead of being read from disk again, `progress` is advanced as files are
+/// found and read, files skipped as binary are recorded in `binaries` and files
+/// that couldn't be read are recorded in `read_errors`.
+pub fn process_files_parallel_with(
+    base_dir: &Path,
+    config: &YekConfig,
+    boost_map: &HashMap<String, i32>,
+    cache: Option<&FileCache>,
+    progress: Option<&ProgressBar>,
+    binaries: Option<&BinaryFiles>,
+    read_errors: Option<&ReadErrors>,
+) -> Result<Vec<ProcessedFile>> {
+    let (walk_builder, gitignore, include) = walk_filters(base_dir, config)?;
 
-    // Build the gitignore
-    let mut gitignore_builder = GitignoreBuilder::new(base_dir);
-    // Add our custom patterns first
-    for pattern in &config.ignore_patterns {
-        gitignore_builder.add_line(None, pattern)?;
-    }
+    // This channel will carry (path, rel_path) of every candidate file out of the walker
+    let (candidates_tx, candidates_rx) = mpsc::channel::<(PathBuf, String)>();
 
-    // If there is a .gitignore in this folder, add it last so its "!" lines override prior patterns
-    let gitignore_file = base_dir.join(".gitignore");
-    if gitignore_file.exists() {
-        gitignore_builder.add(&gitignore_file);
-    }
-
-    let gitignore = Arc::new(gitignore_builder.build()?); // Propagate error here
-
-    // This channel will carry (path, rel_path) to the processing thread
-    let (processed_files_tx, processed_files_rx) = mpsc::channel::<(std::path::PathBuf, String)>();
-
-    // Processing happens on a dedicated thread, to keep from blocking the main walker
-    let process_thread = std::thread::spawn({
-        let priority_rules = config.priority_rules.clone();
-        let boost_map = boost_map.clone();
-        move || {
-            let mut processed = Vec::new();
-            for (path, rel_path) in processed_files_rx {
-                // Read entire file
-                match fs::read(&path) {
-                    Ok(content) => {
-                        // Check if it's binary quickly
-                        if inspect(&content) == ContentType::BINARY {
-                            debug!("Skipping binary file: {rel_path}");
-                            continue;
-                        }
-                        // Compute priority
-                        let rule_priority = get_file_priority(&rel_path, &priority_rules);
-                        let boost = boost_map.get(&rel_path).copied().unwrap_or(0);
-                        let combined = rule_priority + boost;
-                        processed.push(ProcessedFile {
-                            priority: combined,
-                            file_index: 0, // assigned later
-                            rel_path,
-                            content: String::from_utf8_lossy(&content).to_string(),
-                        });
-                    }
-                    Err(e) => {
-                        debug!("Failed to read {rel_path}: {e}");
-                    }
-                }
-            }
-            processed
-        }
-    });
+    // Guards against runaway walks, e.g. over a whole file system. The first limit
+    // crossed stops the walk before any file is read.
+    let max_scan_files = config.max_scan_files;
+    let max_scan_bytes = parse_size_input(&config.max_scan_bytes, false)? as u64;
+    let scanned_files = AtomicUsize::new(0);
+    let scanned_bytes = AtomicU64::new(0);
+    let scan_limit_error: Mutex<Option<String>> = Mutex::new(None);
+    let scan_state = (&scanned_files, &scanned_bytes, &scan_limit_error);
 
     // Use ignore's parallel walker to skip ignored files
     let base_cloned = base_dir.to_owned();
-    let walker_tx = processed_files_tx.clone();
+    let walker_tx = candidates_tx.clone();
+    let walker_progress = progress.cloned();
 
     // Now build the walker (no .gitignore custom filename)
     walk_builder.build_parallel().run(move || {
         let base_dir = base_cloned.clone();
-        let processed_files_tx = walker_tx.clone();
+        let candidates_tx = walker_tx.clone();
         let gitignore = Arc::clone(&gitignore);
+        let include = include.clone();
+        let progress = walker_progress.clone();
+        let (scanned_files, scanned_bytes, scan_limit_error) = scan_state;
 
         Box::new(move |entry| {
             let entry = match entry {
@@ -110,32 +151,63 @@ pub fn process_files_parallel(
             let path = entry.path().to_path_buf();
             let rel_path = normalize_path(&path, &base_dir);
 
-            // If gitignore says skip, we do not even read
-            if gitignore.matched(&path, false).is_ignore() {
-                debug!("Skipping ignored file: {rel_path}");
+            // Ignored files, and files not matching the include patterns, aren't even read
+            if is_excluded(&path, &rel_path, &gitignore, include.as_deref()) {
                 return ignore::WalkState::Continue;
             }
 
-            // Otherwise we send to processing thread
-            processed_files_tx.send((path, rel_path)).ok();
+            // Otherwise it's a candidate to read, unless the walk has grown too large
+            let files = scanned_files.fetch_add(1, Ordering::Relaxed) + 1;
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            let bytes = scanned_bytes.fetch_add(size, Ordering::Relaxed) + size;
+            let exceeded = if max_scan_files > 0 && files > max_scan_files {
+                Some(format!(
+                    "max_scan_files: more than {} files found in {}. Raise max_scan_files, \
+                     or set it to 0, if this is intended",
+                    max_scan_files,
+                    base_dir.display()
+                ))
+            } else if max_scan_bytes > 0 && bytes > max_scan_bytes {
+                Some(format!(
+                    "max_scan_bytes: files in {} add up to more than {}. Raise \
+                     max_scan_bytes, or set it to 0, if this is intended",
+                    base_dir.display(),
+                    ByteSize::b(max_scan_bytes)
+                ))
+            } else {
+                None
+            };
+            if let Some(error) = exceeded {
+                scan_limit_error.lock().unwrap().get_or_insert(error);
+                return ignore::WalkState::Quit;
+            }
+
+            if let Some(progress) = &progress {
+                progress.inc_length(1);
+            }
+            candidates_tx.send((path, rel_path)).ok();
             ignore::WalkState::Continue
         })
     });
 
-    // Drop the sender so the thread can end
-    drop(processed_files_tx);
-
-    // Join the processing thread
-    let mut processed_files = process_thread.join().unwrap();
+    // Drop the sender so the receiver sees the end of the stream
+    drop(candidates_tx);
 
-    // Now assign file_index within each priority group
-    let mut counters = HashMap::new();
-    for f in &mut processed_files {
-        let ctr = counters.entry(f.priority).or_insert(0);
-        f.file_index = *ctr;
-        *ctr += 1;
+    if let Some(error) = scan_limit_error.into_inner().unwrap() {
+        return Err(anyhow!(error));
     }
 
+    let candidates: Vec<_> = candidates_rx.into_iter().collect();
+    let processed_files = process_candidates(
+        candidates,
+        config,
+        boost_map,
+        cache,
+        progress,
+        binaries,
+        read_errors,
+    )?;
+
     if config.debug {
         debug!(
             "Processed {} files in parallel for base_dir: {}",
@@ -144,6 +216,280 @@ pub fn process_files_parallel(
         );
     }
 
+    Ok(processed_files)
+}
+
+/// The walker for `base_dir` with the standard filters set from `config`, along with the
+/// ignore patterns and the include patterns (if any) that walked files are checked against
+fn walk_filters(
+    base_dir: &Path,
+    config: &YekConfig,
+) -> Result<(ignore::WalkBuilder, Arc<Gitignore>, Option<Arc<Gitignore>>)> {
+    let mut walk_builder = ignore::WalkBuilder::new(base_dir);
+
+    let respect_gitignore = config.respect_gitignore.unwrap_or(true);
+
+    // Standard filters. Symlinks are only followed when asked to; the walker detects
+    // symlink loops and reports them as errors, which are skipped.
+    walk_builder
+        .follow_links(config.follow_symlinks)
+        // The walker counts the input directory itself as depth 0, its files as depth 1
+        .max_depth(config.max_depth.map(|depth| depth + 1))
+        .standard_filters(true)
+        .hidden(!config.hidden)
+        .git_ignore(respect_gitignore)
+        .git_global(respect_gitignore)
+        .git_exclude(respect_gitignore)
+        .ignore(respect_gitignore)
+        // .yekignore files use .gitignore syntax and apply to their directory and below
+        .add_custom_ignore_filename(".yekignore")
+        .require_git(false);
+    // Hidden files don't include the repository's own metadata
+    if config.hidden {
+        walk_builder.filter_entry(|entry| entry.file_name() != ".git");
+    }
+
+    // Build the gitignore
+    let mut gitignore_builder = GitignoreBuilder::new(base_dir);
+    // Add our custom patterns first
+    for pattern in &config.ignore_patterns {
+        gitignore_builder.add_line(None, pattern)?;
+    }
+
+    // Paths marked export-ignore in .gitattributes are left out, as `git archive` does
+    if respect_gitignore && base_dir.join(".git").exists() {
+        for pattern in export_ignore_patterns(&base_dir.join(".gitattributes")) {
+            gitignore_builder.add_line(None, &pattern)?;
+        }
+    }
+
+    // Extra ignore files such as .dockerignore, relative to the input directory unless absolute
+    for ignore_file in &config.ignore_files {
+        let path = base_dir.join(ignore_file);
+        if !path.is_file() {
+            debug!("No {} in {}", ignore_file, base_dir.display());
+            continue;
+        }
+        if let Some(e) = gitignore_builder.add(&path) {
+            return Err(anyhow!("ignore_files: {}: {}", path.display(), e));
+        }
+    }
+
+    // If there is a .gitignore in this folder, add it last so its "!" lines override prior patterns
+    let gitignore_file = base_dir.join(".gitignore");
+    if respect_gitignore && gitignore_file.exists() {
+        gitignore_builder.add(&gitignore_file);
+    }
+
+    let gitignore = Arc::new(gitignore_builder.build()?);
+
+    // Include patterns are compiled the same way; a "match" means the file is kept
+    let include = if config.include_patterns.is_empty() {
+        None
+    } else {
+        let mut include_builder = GitignoreBuilder::new(base_dir);
+        for pattern in &config.include_patterns {
+            include_builder.add_line(None, pattern)?;
+        }
+        Some(Arc::new(include_builder.build()?))
+    };
+
+    Ok((walk_builder, gitignore, include))
+}
+
+/// Whether a walked file is left out: it doesn't match the include patterns, or it's
+/// ignored. Parent directories are checked too so a plain directory name excludes
+/// everything below it.
+fn is_excluded(
+    path: &Path,
+    rel_path: &str,
+    gitignore: &Gitignore,
+    include: Option<&Gitignore>,
+) -> bool {
+    if let Some(include) = include {
+        if !include.matched_path_or_any_parents(path, false).is_ignore() {
+            debug!("Skipping file not matching include patterns: {rel_path}");
+            return true;
+        }
+    }
+    if gitignore
+        .matched_path_or_any_parents(path, false)
+        .is_ignore()
+    {
+        debug!("Skipping ignored file: {rel_path}");
+        return true;
+    }
+    false
+}
+
+/// Paths of the files walking `base_dir` finds, sorted: the same files
+/// `process_files_parallel` would read, without the scan limits.
+pub fn walk_files(base_dir: &Path, config: &YekConfig) -> Result<Vec<PathBuf>> {
+    let (walk_builder, gitignore, include) = walk_filters(base_dir, config)?;
+    let mut files = walk_builder
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()))
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            let rel_path = normalize_path(path, base_dir);
+            !is_excluded(path, &rel_path, &gitignore, include.as_deref())
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+/// Patterns of a .gitattributes file that set the `export-ignore` attribute. A missing or
+/// unreadable file has none.
+pub fn export_ignore_patterns(gitattributes: &Path) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(gitattributes) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pattern = fields.next()?;
+            fields
+                .any(|attr| attr == "export-ignore")
+                .then(|| pattern.to_string())
+        })
+        .collect()
+}
+
+/// Process an explicit list of files (e.g. read from stdin) instead of walking a directory.
+/// Relative paths are resolved against `base_dir`, and every path is reported relative to it.
+/// Ignore patterns are not applied: the caller asked for exactly these files.
+/// Files skipped as binary are recorded in `binaries`, unreadable ones in `read_errors`.
+pub fn process_file_list(
+    base_dir: &Path,
+    paths: &[PathBuf],
+    config: &YekConfig,
+    boost_map: &HashMap<String, i32>,
+    binaries: Option<&BinaryFiles>,
+    read_errors: Option<&ReadErrors>,
+) -> Result<Vec<ProcessedFile>> {
+    let candidates = paths
+        .iter()
+        .map(|p| {
+            let path = if p.is_absolute() {
+                p.clone()
+            } else {
+                base_dir.join(p)
+            };
+            let rel_path = normalize_path(&path, base_dir);
+            (path, rel_path)
+        })
+        .filter(|(path, rel_path)| {
+            let is_file = path.is_file();
+            if !is_file {
+                debug!("Skipping missing or non-file path: {rel_path}");
+            }
+            is_file
+        })
+        .collect();
+
+    process_candidates(
+        candidates,
+        config,
+        boost_map,
+        None,
+        None,
+        binaries,
+        read_errors,
+    )
+}
+
+/// Read candidate files, skip oversized files, binaries and unreadable files, and compute
+/// priorities in parallel. Returns files sorted by priority desc, then file_index.
+/// With `strict` set, a file that can't be read fails the whole run instead.
+fn process_candidates(
+    mut candidates: Vec<(PathBuf, String)>,
+    config: &YekConfig,
+    boost_map: &HashMap<String, i32>,
+    cache: Option<&FileCache>,
+    progress: Option<&ProgressBar>,
+    binaries: Option<&BinaryFiles>,
+    read_errors: Option<&ReadErrors>,
+) -> Result<Vec<ProcessedFile>> {
+    let own_read_errors = ReadErrors::default();
+    let read_errors = read_errors.unwrap_or(&own_read_errors);
+
+    // Input order may be nondeterministic, sort so file_index assignment is stable
+    candidates.sort_by(|a, b| a.1.cmp(&b.1));
+    candidates.dedup_by(|a, b| a.1 == b.1);
+
+    let max_file_size = config
+        .max_file_size
+        .as_deref()
+        .map(|size| parse_size_input(size, false))
+        .transpose()?;
+
+    let bytes_read = AtomicU64::new(0);
+    let priority_rules = with_default_syntax(&config.priority_rules, &config.priority_rule_syntax);
+
+    // Read files, skip binaries and compute priorities in parallel
+    let mut processed_files: Vec<ProcessedFile> = candidates
+        .into_par_iter()
+        .filter_map(|(path, rel_path)| {
+            if let Some(progress) = progress {
+                progress.inc(1);
+            }
+            // Skip files over the size limit without reading them
+            if let Some(max_file_size) = max_file_size {
+                let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                if size > max_file_size as u64 {
+                    debug!("Skipping file over max_file_size ({size} bytes): {rel_path}");
+                    return None;
+                }
+            }
+            // Reuse the cached content when the file hasn't changed since the last run
+            let read = || read_text_file(&path, &rel_path, config, binaries, read_errors);
+            let content = match cache {
+                Some(cache) => cache.get_or_read(&path, read),
+                None => read().ok().flatten(),
+            }?;
+            if let Some(progress) = progress {
+                let total = bytes_read.fetch_add(content.len() as u64, Ordering::Relaxed)
+                    + content.len() as u64;
+                progress.set_message(format!("{} read", ByteSize::b(total)));
+            }
+            // Compute priority
+            let rule_priority = get_file_priority_with_extensions(
+                &rel_path,
+                &priority_rules,
+                &config.extension_priorities,
+                &config.priority_combine,
+            );
+            let depth = depth_priority(&rel_path, config.depth_priority);
+            let boost = boost_map.get(&rel_path).copied().unwrap_or(0);
+            Some(ProcessedFile {
+                priority: rule_priority + depth + boost,
+                file_index: 0, // assigned later
+                rel_path,
+                content,
+                commit: None,
+            })
+        })
+        .collect();
+
+    if config.strict {
+        if let Some((rel_path, error)) = read_errors.first() {
+            return Err(anyhow!("Failed to read {}: {}", rel_path, error));
+        }
+    }
+
+    // Now assign file_index within each priority group
+    let mut counters = HashMap::new();
+    for f in &mut processed_files {
+        let ctr = counters.entry(f.priority).or_insert(0);
+        f.file_index = *ctr;
+        *ctr += 1;
+    }
+
     // Sort by priority desc, then file_index
     processed_files.par_sort_by(|a, b| {
         a.priority
@@ -155,12 +501,113 @@ pub fn process_files_parallel(
     Ok(processed_files)
 }
 
-/// Create a relative, slash-normalized path
+/// Read a file as text, returning `None` if it looks binary. Read failures and text that
+/// doesn't decode with the configured encoding are recorded in `read_errors` and returned
+/// as errors.
+fn read_text_file(
+    path: &Path,
+    rel_path: &str,
+    config: &YekConfig,
+    binaries: Option<&BinaryFiles>,
+    read_errors: &ReadErrors,
+) -> io::Result<Option<String>> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) => {
+            debug!("Skipping unreadable file {rel_path}: {e}");
+            read_errors.record(rel_path.to_string(), e.to_string());
+            return Err(e);
+        }
+    };
+    // Check if it's binary quickly, unless its extension says it's text anyway
+    if !has_extension(path, &config.force_text_extensions)
+        && is_binary_content(
+            &content,
+            config
+                .binary_scan_bytes
+                .unwrap_or(DEFAULT_BINARY_SCAN_BYTES),
+            config
+                .null_byte_tolerance
+                .unwrap_or(DEFAULT_NULL_BYTE_TOLERANCE),
+        )
+    {
+        debug!("Skipping binary file: {rel_path}");
+        if let Some(binaries) = binaries {
+            let extension = path.extension().and_then(|e| e.to_str());
+            let reason = match extension {
+                Some(ext) if config.binary_extensions.iter().any(|bin| bin == ext) => {
+                    BinaryReason::Extension
+                }
+                _ => BinaryReason::Content,
+            };
+            binaries.record(rel_path.to_string(), reason);
+        }
+        return Ok(None);
+    }
+    Ok(match config.encoding.as_deref() {
+        // A byte-order mark (UTF-8 or UTF-16) gives the encoding away and is left out of
+        // the text, so it doesn't end up in the output as a stray character
+        None if Encoding::for_bom(&content).is_some() => Some(
+            decode_text(&content, "utf-8")
+                .unwrap_or_else(|| String::from_utf8_lossy(&content).to_string()),
+        ),
+        None => Some(String::from_utf8_lossy(&content).to_string()),
+        // Text that doesn't decode is an error like a failed read, so it isn't cached
+        Some(encoding) => match decode_text(&content, encoding) {
+            Some(text) => Some(text),
+            None => {
+                let e = io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("not valid {encoding} text"),
+                );
+                read_errors.record(rel_path.to_string(), e.to_string());
+                return Err(e);
+            }
+        },
+    })
+}
+
+/// Whether the file name ends in one of `extensions`, given as "svg", ".svg" or "min.js",
+/// ignoring case
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let name = name.to_ascii_lowercase();
+    extensions.iter().any(|ext| {
+        let ext = ext.trim_start_matches('.').to_ascii_lowercase();
+        name.strip_suffix(&ext)
+            .is_some_and(|stem| stem.len() > 1 && stem.ends_with('.'))
+    })
+}
+
+/// Create a relative, slash-normalized path. `.` components are dropped and `..`
+/// components are resolved lexically, without touching the file system.
 pub fn normalize_path(path: &Path, base: &Path) -> String {
-    path.strip_prefix(base)
-        .unwrap_or(path)
-        .to_path_buf()
+    let path = collapse_dots(path);
+    path.strip_prefix(collapse_dots(base))
+        .unwrap_or(&path)
         .to_slash()
         .unwrap_or_default()
         .to_string()
 }
+
+/// Drop `.` components and resolve `..` against the preceding component. Leading `..`
+/// of a relative path are kept, and `..` never climbs above a root or drive prefix.
+fn collapse_dots(path: &Path) -> PathBuf {
+    let mut components: Vec<Component> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match components.last() {
+                Some(Component::Normal(_)) => {
+                    components.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => components.push(component),
+            },
+            _ => components.push(component),
+        }
+    }
+    components.iter().collect()
+}
@@ -1,27 +1,236 @@
+use anyhow::{anyhow, Result};
 use git2;
-use regex;
+use globset::{GlobBuilder, GlobMatcher};
+use normalize_path::NormalizePath;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{OnceLock, RwLock},
+    time::UNIX_EPOCH,
+};
 use tracing::debug;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct PriorityRule {
     pub pattern: String,
     pub score: i32,
+    /// Only match files with one of these extensions ("rs" or ".rs"). Empty matches any file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    /// Regex of paths to leave out even when `pattern` matches, e.g. ".*_test\\.rs"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude_pattern: Option<String>,
+    /// How `pattern` is written: "regex" or "glob" (e.g. "src/**/*.rs"). Rules without
+    /// one use the configured priority_rule_syntax, and regex outside of a config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syntax: Option<String>,
 }
 
-/// Determine final priority of a file by scanning the priority list
-/// in descending order of score.
-pub fn get_file_priority(path: &str, rules: &[PriorityRule]) -> i32 {
-    let mut priority = 0;
-    for rule in rules {
-        if let Ok(re) = regex::Regex::new(&rule.pattern) {
-            if re.is_match(path) {
-                priority += rule.score;
-            }
+impl PriorityRule {
+    /// Check if `path` matches this rule's pattern, has one of its extensions
+    /// and does not match its exclude pattern. Invalid patterns never match.
+    pub fn matches(&self, path: &str) -> bool {
+        let glob = self.syntax.as_deref() == Some("glob");
+        compiled_pattern(&self.pattern, glob).is_some_and(|p| p.is_match(path))
+            && self.matches_extension(path)
+            && !self
+                .exclude_pattern
+                .as_deref()
+                .and_then(|exclude| compiled_pattern(exclude, false))
+                .is_some_and(|p| p.is_match(path))
+    }
+
+    fn matches_extension(&self, path: &str) -> bool {
+        if self.extensions.is_empty() {
+            return true;
         }
+        let Some(ext) = Path::new(path).extension().and_then(|e| e.to_str()) else {
+            return false;
+        };
+        self.extensions
+            .iter()
+            .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(ext))
+    }
+}
+
+/// A band of priorities, labelled in the output with `tier_headers`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PriorityTier {
+    /// Shown as a "=== {name} ===" banner before the tier's first file
+    pub name: String,
+    /// Lowest priority of a file in the tier
+    pub min_priority: i32,
+}
+
+/// Name of the tier `priority` falls in: the one with the highest `min_priority` that
+/// isn't above it. None when the priority is below every tier.
+pub fn priority_tier(priority: i32, tiers: &[PriorityTier]) -> Option<&str> {
+    tiers
+        .iter()
+        .filter(|tier| tier.min_priority <= priority)
+        .max_by_key(|tier| tier.min_priority)
+        .map(|tier| tier.name.as_str())
+}
+
+/// Give rules that don't set their own `syntax` the configured one
+pub fn with_default_syntax(rules: &[PriorityRule], syntax: &str) -> Vec<PriorityRule> {
+    rules
+        .iter()
+        .map(|rule| PriorityRule {
+            syntax: Some(rule.syntax.clone().unwrap_or_else(|| syntax.to_string())),
+            ..rule.clone()
+        })
+        .collect()
+}
+
+/// Compile a glob the way priority rules use it: `*` stays within a path component
+/// and `**` crosses directories
+pub fn compile_glob(pattern: &str) -> Result<GlobMatcher, globset::Error> {
+    Ok(GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()?
+        .compile_matcher())
+}
+
+#[derive(Clone)]
+enum CompiledPattern {
+    Regex(Regex),
+    Glob(GlobMatcher),
+}
+
+impl CompiledPattern {
+    fn is_match(&self, path: &str) -> bool {
+        match self {
+            CompiledPattern::Regex(re) => re.is_match(path),
+            CompiledPattern::Glob(glob) => glob.is_match(path),
+        }
+    }
+}
+
+// Compiled rule patterns, keyed by pattern and whether it's a glob, shared across threads
+// so each pattern is compiled only once
+type PatternCache = RwLock<HashMap<(String, bool), Option<CompiledPattern>>>;
+static PATTERN_CACHE: OnceLock<PatternCache> = OnceLock::new();
+
+fn compiled_pattern(pattern: &str, glob: bool) -> Option<CompiledPattern> {
+    let cache = PATTERN_CACHE.get_or_init(Default::default);
+    let key = (pattern.to_string(), glob);
+    if let Some(compiled) = cache.read().unwrap().get(&key) {
+        return compiled.clone();
+    }
+    let compiled = if glob {
+        compile_glob(pattern).ok().map(CompiledPattern::Glob)
+    } else {
+        Regex::new(pattern).ok().map(CompiledPattern::Regex)
+    };
+    if compiled.is_none() {
+        debug!("Invalid priority rule pattern: {}", pattern);
+    }
+    cache.write().unwrap().insert(key, compiled.clone());
+    compiled
+}
+
+/// Determine the priority of a file: the sum of the scores of all matching rules
+pub fn get_file_priority(path: &str, rules: &[PriorityRule]) -> i32 {
+    get_file_priority_with(path, rules, "sum")
+}
+
+/// Determine the priority of a file, combining the scores of matching rules with
+/// `combine` (see `SUPPORTED_PRIORITY_COMBINE`): "sum" adds them up, "max" takes the
+/// highest. Files matching no rule get 0.
+pub fn get_file_priority_with(path: &str, rules: &[PriorityRule], combine: &str) -> i32 {
+    rule_priority(path, rules, combine).unwrap_or(0)
+}
+
+/// Same as `get_file_priority_with`, but a file matching no rule gets the score of its
+/// extension in `extension_priorities` (keys like "rs" or ".rs", in any case), or 0.
+pub fn get_file_priority_with_extensions(
+    path: &str,
+    rules: &[PriorityRule],
+    extension_priorities: &HashMap<String, i32>,
+    combine: &str,
+) -> i32 {
+    rule_priority(path, rules, combine)
+        .or_else(|| extension_priority(path, extension_priorities))
+        .unwrap_or(0)
+}
+
+/// Score for how deep `path` is nested (see `depth_priority`): `per_level` taken off for
+/// every directory above the file, so a positive value favors files near the root
+pub fn depth_priority(path: &str, per_level: i32) -> i32 {
+    let depth = path.matches('/').count() as i32;
+    depth.saturating_mul(per_level).saturating_neg()
+}
+
+/// How a file's priority comes out of the rules, see `explain_file_priority`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PriorityExplanation {
+    /// Pattern of the highest-scoring matching rule (the first one on a tie), e.g.
+    /// "^src/", or "extension .rs" when only `extension_priorities` gave a score
+    pub rule: Option<String>,
+    /// Score of that rule or extension, 0 without one
+    pub rule_score: i32,
+    /// Priority before any Git boost, as `get_file_priority_with_extensions` gives it
+    pub priority: i32,
+}
+
+/// Same as `get_file_priority_with_extensions`, but also tells which rule won
+pub fn explain_file_priority(
+    path: &str,
+    rules: &[PriorityRule],
+    extension_priorities: &HashMap<String, i32>,
+    combine: &str,
+) -> PriorityExplanation {
+    let winner = rules
+        .iter()
+        .filter(|rule| rule.matches(path))
+        .reduce(|best, rule| if rule.score > best.score { rule } else { best });
+    if let Some(rule) = winner {
+        return PriorityExplanation {
+            rule: Some(rule.pattern.clone()),
+            rule_score: rule.score,
+            priority: rule_priority(path, rules, combine).unwrap_or(rule.score),
+        };
     }
-    priority
+    match extension_priority(path, extension_priorities) {
+        Some(score) => PriorityExplanation {
+            rule: Path::new(path)
+                .extension()
+                .map(|ext| format!("extension .{}", ext.to_string_lossy())),
+            rule_score: score,
+            priority: score,
+        },
+        None => PriorityExplanation {
+            rule: None,
+            rule_score: 0,
+            priority: 0,
+        },
+    }
+}
+
+/// Combined score of the rules matching `path`, or `None` when no rule matches
+fn rule_priority(path: &str, rules: &[PriorityRule], combine: &str) -> Option<i32> {
+    let mut scores = rules
+        .iter()
+        .filter(|rule| rule.matches(path))
+        .map(|rule| rule.score)
+        .peekable();
+    scores.peek()?;
+    Some(match combine {
+        "max" => scores.max().unwrap_or(0),
+        _ => scores.sum(),
+    })
+}
+
+fn extension_priority(path: &str, extension_priorities: &HashMap<String, i32>) -> Option<i32> {
+    let ext = Path::new(path).extension()?.to_str()?;
+    extension_priorities
+        .iter()
+        .find(|(e, _)| e.trim_start_matches('.').eq_ignore_ascii_case(ext))
+        .map(|(_, score)| *score)
 }
 
 /// Rank-based approach to compute how "recent" each file is (0=oldest, 1=newest).
@@ -70,30 +279,98 @@ pub fn compute_recentness_boost(
     result
 }
 
+/// Git boost blending how recently each file changed with how often it changed.
+/// `frequency_weight` (0.0..1.0) is the share of the boost that comes from the number of
+/// commits touching the file, ranked like commit times in `compute_recentness_boost`;
+/// the rest comes from recency. With a weight of 0 this is `compute_recentness_boost`.
+pub fn compute_git_boost(
+    commit_times: &HashMap<String, u64>,
+    commit_counts: &HashMap<String, usize>,
+    max_boost: i32,
+    frequency_weight: f64,
+) -> HashMap<String, i32> {
+    let recency = compute_recentness_boost(commit_times, max_boost);
+    if frequency_weight <= 0.0 {
+        return recency;
+    }
+    let counts = commit_counts
+        .iter()
+        .map(|(path, count)| (path.clone(), *count as u64))
+        .collect();
+    let frequency = compute_recentness_boost(&counts, max_boost);
+    recency
+        .into_iter()
+        .map(|(path, boost)| {
+            let frequency_boost = frequency.get(&path).copied().unwrap_or(0) as f64;
+            let blended =
+                (1.0 - frequency_weight) * boost as f64 + frequency_weight * frequency_boost;
+            (path, blended.round() as i32)
+        })
+        .collect()
+}
+
+/// What the last `max_commits` commits say about each file (path relative to the
+/// directory the history was gathered for), gathered in a single walk of the history by
+/// `get_git_history_git2`
+#[derive(Debug, Clone, Default)]
+pub struct GitHistory {
+    /// Unix time of the most recent commit touching the file
+    pub times: HashMap<String, u64>,
+    /// Number of commits touching the file
+    pub counts: HashMap<String, usize>,
+    /// Short (7 character) hash of the most recent commit touching the file
+    pub hashes: HashMap<String, String>,
+}
+
+impl GitHistory {
+    /// Add the files of another repository's history
+    pub fn extend(&mut self, other: GitHistory) {
+        self.times.extend(other.times);
+        self.counts.extend(other.counts);
+        self.hashes.extend(other.hashes);
+    }
+}
+
 /// Get the commit time of the most recent change to each file using git2.
-/// Returns a map from file path (relative to the repo root) → last commit Unix time.
+/// Returns a map from file path (relative to `repo_path`) → last commit Unix time.
 /// If Git or .git folder is missing, returns None instead of erroring.
-/// Only considers up to `max_commits` most recent commits.
+/// Only considers up to `max_commits` most recent commits. See `get_git_history_git2`
+/// for the times, counts and hashes from one walk.
 pub fn get_recent_commit_times_git2(
     repo_path: &Path,
     max_commits: usize,
 ) -> Option<HashMap<String, u64>> {
-    // Walk up until you find a .git folder but not higher than the base of the given repo_path
-    let mut current_path = repo_path.to_path_buf();
-    while current_path.components().count() > 1 {
-        if current_path.join(".git").exists() {
-            break;
-        }
-        current_path = current_path.parent()?.to_path_buf();
-    }
+    Some(get_git_history_git2(repo_path, max_commits)?.times)
+}
 
-    let repo = match git2::Repository::open(&current_path) {
-        Ok(repo) => repo,
-        Err(_) => {
-            debug!("Not a Git repository or unable to open: {:?}", current_path);
-            return None;
-        }
-    };
+/// Number of commits touching each file among the last `max_commits`. See
+/// `get_git_history_git2` for the times, counts and hashes from one walk.
+pub fn get_recent_commit_counts_git2(
+    repo_path: &Path,
+    max_commits: usize,
+) -> Option<HashMap<String, usize>> {
+    Some(get_git_history_git2(repo_path, max_commits)?.counts)
+}
+
+/// Short (7 character) hash of the most recent commit touching each file. Files not changed
+/// within the last `max_commits` commits are left out. See `get_git_history_git2` for the
+/// times, counts and hashes from one walk.
+pub fn get_recent_commit_hashes_git2(
+    repo_path: &Path,
+    max_commits: usize,
+) -> Option<HashMap<String, String>> {
+    Some(get_git_history_git2(repo_path, max_commits)?.hashes)
+}
+
+/// Walk the last `max_commits` commits once and gather the time, hash and number of
+/// commits for each file under `repo_path` they touched, keyed by the path relative to
+/// `repo_path`, which may be below the repository root. Returns None without Git history.
+///
+/// Each commit is diffed against its first parent so a file is only attributed
+/// to commits that actually touched it. Merge commits are skipped and renames
+/// are not followed, mirroring `git log --no-merges --no-renames`.
+pub fn get_git_history_git2(repo_path: &Path, max_commits: usize) -> Option<GitHistory> {
+    let (repo, current_path, prefix) = open_repo(repo_path)?;
 
     let mut revwalk = match repo.revwalk() {
         Ok(revwalk) => revwalk,
@@ -112,7 +389,7 @@ pub fn get_recent_commit_times_git2(
     }
     revwalk.set_sorting(git2::Sort::TIME).ok()?;
 
-    let mut commit_times = HashMap::new();
+    let mut history = GitHistory::default();
     for oid_result in revwalk.take(max_commits) {
         let oid = match oid_result {
             Ok(oid) => oid,
@@ -129,6 +406,12 @@ pub fn get_recent_commit_times_git2(
                 continue;
             }
         };
+
+        // Skip merge commits, like `git log --no-merges`
+        if commit.parent_count() > 1 {
+            continue;
+        }
+
         let tree = match commit.tree() {
             Ok(tree) => tree,
             Err(e) => {
@@ -137,18 +420,170 @@ pub fn get_recent_commit_times_git2(
             }
         };
 
+        // Root commits are diffed against an empty tree
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => match parent.tree() {
+                Ok(parent_tree) => Some(parent_tree),
+                Err(e) => {
+                    debug!("Failed to get parent tree for commit {:?}: {:?}", oid, e);
+                    continue;
+                }
+            },
+            Err(_) => None,
+        };
+
+        let diff = match repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None) {
+            Ok(diff) => diff,
+            Err(e) => {
+                debug!("Failed to diff commit {:?}: {:?}", oid, e);
+                continue;
+            }
+        };
+
+        // Revwalk is newest-first, so the first time we see a path is its latest change
         let time = commit.time().seconds() as u64;
-        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+        for delta in diff.deltas() {
+            let path = delta
+                .new_file()
+                .path()
+                .and_then(|p| p.strip_prefix(&prefix).ok());
+            if let Some(path) = path.and_then(|p| p.to_str()) {
+                *history.counts.entry(path.to_string()).or_insert(0) += 1;
+                if !history.times.contains_key(path) {
+                    let mut hash = oid.to_string();
+                    hash.truncate(7);
+                    history.times.insert(path.to_string(), time);
+                    history.hashes.insert(path.to_string(), hash);
+                }
+            }
+        }
+    }
+
+    Some(history)
+}
+
+/// Modification times (seconds since the Unix epoch) of `rel_paths` under `base`, keyed
+/// like Git commit times so they can stand in for them when there is no Git history.
+/// Files whose modification time can't be read are left out.
+pub fn get_file_mtimes<'a>(
+    base: &Path,
+    rel_paths: impl IntoIterator<Item = &'a str>,
+) -> HashMap<String, u64> {
+    rel_paths
+        .into_iter()
+        .filter_map(|rel_path| {
+            let modified = fs::metadata(base.join(rel_path)).ok()?.modified().ok()?;
+            let secs = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+            Some((rel_path.to_string(), secs))
+        })
+        .collect()
+}
+
+/// List every file under `repo_path` tracked in the HEAD commit, as paths relative to
+/// `repo_path`. Returns None if `repo_path` is not inside a Git repository or has no commits.
+pub fn get_tracked_files_git2(repo_path: &Path) -> Option<HashSet<String>> {
+    let (repo, current_path, prefix) = open_repo(repo_path)?;
+    let tree = match repo.head().and_then(|head| head.peel_to_tree()) {
+        Ok(tree) => tree,
+        Err(e) => {
+            debug!("Unable to read HEAD tree: {:?} in {:?}", e, current_path);
+            return None;
+        }
+    };
+
+    let mut tracked = HashSet::new();
+    tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() == Some(git2::ObjectType::Blob) {
             if let Some(name) = entry.name() {
-                if entry.kind() == Some(git2::ObjectType::Blob) {
-                    let full_path = format!("{}{}", root, name);
-                    commit_times.entry(full_path).or_insert(time);
+                let path = format!("{dir}{name}");
+                if let Some(path) = Path::new(&path)
+                    .strip_prefix(&prefix)
+                    .ok()
+                    .and_then(|p| p.to_str())
+                {
+                    tracked.insert(path.to_string());
                 }
             }
-            git2::TreeWalkResult::Ok
+        }
+        git2::TreeWalkResult::Ok
+    })
+    .ok()?;
+
+    Some(tracked)
+}
+
+/// Files that differ between `base` (any revision, e.g. "HEAD" or "origin/main") and the
+/// working tree, staged or not, relative to `repo_path`. Untracked files are included when
+/// `include_untracked` is set. Files deleted since `base` aren't listed.
+pub fn get_changed_files_git2(
+    repo_path: &Path,
+    base: &str,
+    include_untracked: bool,
+) -> Result<HashSet<String>> {
+    let (repo, _, prefix) = open_repo(repo_path)
+        .ok_or_else(|| anyhow!("diff: {} is not in a Git repository", repo_path.display()))?;
+    let tree = repo
+        .revparse_single(base)
+        .and_then(|object| object.peel_to_tree())
+        .map_err(|e| anyhow!("diff: Unknown revision '{}': {}", base, e.message()))?;
+
+    let mut options = git2::DiffOptions::new();
+    options
+        .include_untracked(include_untracked)
+        .recurse_untracked_dirs(include_untracked);
+    let diff = repo
+        .diff_tree_to_workdir_with_index(Some(&tree), Some(&mut options))
+        .map_err(|e| anyhow!("diff: Failed to diff against '{}': {}", base, e.message()))?;
+
+    // Paths in the diff are relative to the repository root, which may be above repo_path
+    Ok(diff
+        .deltas()
+        .filter(|delta| delta.status() != git2::Delta::Deleted)
+        .filter_map(|delta| {
+            delta
+                .new_file()
+                .path()?
+                .strip_prefix(&prefix)
+                .ok()?
+                .to_str()
         })
-        .ok()?;
+        .map(|path| path.to_string())
+        .collect())
+}
+
+/// Hash of the commit HEAD points to in the repository containing `repo_path`, if any
+pub fn get_head_commit_git2(repo_path: &Path) -> Option<String> {
+    let (repo, _, _) = open_repo(repo_path)?;
+    let head = repo.head().ok()?.target()?;
+    Some(head.to_string())
+}
+
+/// Open the Git repository containing `repo_path`. Returns the repository along with the
+/// directory it was found in and the path of `repo_path` below that directory (empty when
+/// it is the repository root), since Git gives paths relative to the root.
+fn open_repo(repo_path: &Path) -> Option<(git2::Repository, PathBuf, PathBuf)> {
+    // Walk up until you find a .git folder. Starting from the absolute path lets a relative
+    // repo_path such as "src" find the repository above it.
+    let repo_path = std::path::absolute(repo_path).ok()?.normalize();
+    let mut current_path = repo_path.clone();
+    while current_path.components().count() > 1 {
+        if current_path.join(".git").exists() {
+            break;
+        }
+        current_path = current_path.parent()?.to_path_buf();
     }
 
-    Some(commit_times)
+    match git2::Repository::open(&current_path) {
+        Ok(repo) => {
+            let prefix = repo_path
+                .strip_prefix(&current_path)
+                .unwrap_or(Path::new(""))
+                .to_path_buf();
+            Some((repo, current_path, prefix))
+        }
+        Err(_) => {
+            debug!("Not a Git repository or unable to open: {:?}", current_path);
+            None
+        }
+    }
 }
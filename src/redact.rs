@@ -0,0 +1,106 @@
+use crate::defaults::{REDACT_ENTROPY_THRESHOLD, REDACT_MIN_ENTROPY_LEN, SECRET_PATTERNS};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::borrow::Cow;
+
+/// What a secret is replaced with
+pub const REDACTED: &str = "***REDACTED***";
+
+/// Masks secrets in file content: matches of `SECRET_PATTERNS` and of custom patterns,
+/// then long base64-like strings with high entropy.
+pub struct Redactor {
+    patterns: Vec<Regex>,
+    candidates: Regex,
+}
+
+impl Redactor {
+    /// A redactor for the built-in patterns followed by `custom` ones (`redact_patterns`)
+    pub fn new(custom: &[String]) -> Result<Self> {
+        let mut patterns = SECRET_PATTERNS
+            .iter()
+            .map(|pattern| Regex::new(pattern).expect("built-in secret patterns are valid"))
+            .collect::<Vec<_>>();
+        for pattern in custom {
+            patterns.push(
+                Regex::new(pattern).map_err(|e| {
+                    anyhow!("redact_patterns: Invalid pattern '{}': {}", pattern, e)
+                })?,
+            );
+        }
+        let candidates = Regex::new(&format!("[A-Za-z0-9+/=_-]{{{},}}", REDACT_MIN_ENTROPY_LEN))
+            .expect("entropy candidate pattern is valid");
+        Ok(Self {
+            patterns,
+            candidates,
+        })
+    }
+
+    /// `text` with every secret replaced by `REDACTED`, and the number of secrets replaced
+    pub fn redact<'a>(&self, text: &'a str) -> (Cow<'a, str>, usize) {
+        let mut text = Cow::Borrowed(text);
+        let mut count = 0;
+        for pattern in &self.patterns {
+            if let Some(masked) = mask(pattern, &text, &mut count, |_| true) {
+                text = Cow::Owned(masked);
+            }
+        }
+        if let Some(masked) = mask(&self.candidates, &text, &mut count, is_high_entropy) {
+            text = Cow::Owned(masked);
+        }
+        (text, count)
+    }
+}
+
+/// Replace the matches of `pattern` in `text` that `is_secret` accepts, or only their first
+/// capture group when the pattern has one. Returns None when nothing was replaced.
+fn mask(
+    pattern: &Regex,
+    text: &str,
+    count: &mut usize,
+    is_secret: impl Fn(&str) -> bool,
+) -> Option<String> {
+    let mut masked = String::new();
+    let mut last = 0;
+    for captures in pattern.captures_iter(text) {
+        let Some(secret) = captures.get(1).or_else(|| captures.get(0)) else {
+            continue;
+        };
+        if !is_secret(secret.as_str()) {
+            continue;
+        }
+        masked.push_str(&text[last..secret.start()]);
+        masked.push_str(REDACTED);
+        last = secret.end();
+        *count += 1;
+    }
+    if last == 0 {
+        return None;
+    }
+    masked.push_str(&text[last..]);
+    Some(masked)
+}
+
+/// Whether a run of base64-like characters looks random enough to be a key: it mixes
+/// upper and lower case letters and digits, and its Shannon entropy is over the threshold
+fn is_high_entropy(candidate: &str) -> bool {
+    let mixed = candidate.bytes().any(|b| b.is_ascii_uppercase())
+        && candidate.bytes().any(|b| b.is_ascii_lowercase())
+        && candidate.bytes().any(|b| b.is_ascii_digit());
+    if !mixed {
+        return false;
+    }
+    let mut counts = [0_usize; 256];
+    for b in candidate.bytes() {
+        counts[b as usize] += 1;
+    }
+    let len = candidate.len() as f64;
+    let entropy: f64 = counts
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum();
+    entropy > REDACT_ENTROPY_THRESHOLD
+}
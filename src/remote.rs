@@ -0,0 +1,88 @@
+use anyhow::{anyhow, Result};
+use git2::{build::RepoBuilder, FetchOptions};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tracing::debug;
+
+/// URL schemes that mark an input as a remote repository rather than a directory
+const URL_SCHEMES: &[&str] = &["https://", "http://", "ssh://", "git://", "file://"];
+
+/// Whether an input looks like a git URL: one of the usual schemes, or the scp-like
+/// `user@host:path` form as long as no local path of that name exists.
+pub fn is_git_url(input: &str) -> bool {
+    if URL_SCHEMES.iter().any(|scheme| input.starts_with(scheme)) {
+        return true;
+    }
+    input
+        .split_once(':')
+        .is_some_and(|(host, path)| host.contains('@') && !host.contains('/') && !path.is_empty())
+        && !Path::new(input).exists()
+}
+
+/// Repository name from its URL, e.g. "repo" for "https://github.com/org/repo.git"
+pub fn repo_name_from_url(url: &str) -> String {
+    let path = url.trim_end_matches('/');
+    let name = path.rsplit(['/', ':']).next().unwrap_or(path);
+    let name = name.strip_suffix(".git").unwrap_or(name);
+    if name.is_empty() {
+        "repo".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// A clone of a remote repository in the system temp dir. The clone is removed when
+/// this is dropped, unless it was made with `keep` set.
+pub struct RemoteClone {
+    root: PathBuf,
+    path: PathBuf,
+    keep: bool,
+}
+
+impl RemoteClone {
+    /// Directory holding the checkout, named after the repository
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RemoteClone {
+    fn drop(&mut self) {
+        if !self.keep {
+            if let Err(e) = fs::remove_dir_all(&self.root) {
+                debug!("Failed to remove clone at {}: {}", self.root.display(), e);
+            }
+        }
+    }
+}
+
+/// Shallow-clone `url` (only its latest commit) into a new directory in the system temp dir
+pub fn clone_remote(url: &str, keep: bool) -> Result<RemoteClone> {
+    static CLONES: AtomicUsize = AtomicUsize::new(0);
+    let root = std::env::temp_dir().join(format!(
+        "yek-clone-{}-{}",
+        std::process::id(),
+        CLONES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let clone = RemoteClone {
+        path: root.join(repo_name_from_url(url)),
+        root,
+        keep,
+    };
+
+    let mut fetch_options = FetchOptions::new();
+    // libgit2's local transport can't make shallow clones
+    if !url.starts_with("file://") {
+        fetch_options.depth(1);
+    }
+    debug!("Cloning {} into {}", url, clone.path.display());
+    RepoBuilder::new()
+        .fetch_options(fetch_options)
+        .clone(url, &clone.path)
+        .map_err(|e| anyhow!("input_dirs: failed to clone '{}': {}", url, e.message()))?;
+
+    Ok(clone)
+}
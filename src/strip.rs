@@ -0,0 +1,242 @@
+use crate::language_for_path;
+use std::path::Path;
+
+/// Comment and string syntax of a language, as far as stripping comments needs it
+struct Syntax {
+    /// Line comment marker. A `#` only starts a comment at line start or after whitespace.
+    line_comment: Option<&'static str>,
+    /// `/* ... */` block comments
+    block_comments: bool,
+    /// Characters that delimit string literals. Backslash escapes are honored.
+    quotes: &'static [char],
+    /// Whether `quotes` strings may span lines (shell-like languages)
+    multiline_strings: bool,
+    /// Python `"""` and `'''` strings
+    triple_quotes: bool,
+    /// Rust char literals (as opposed to lifetimes) and raw strings
+    rust_literals: bool,
+}
+
+const C_LIKE: Syntax = Syntax {
+    line_comment: Some("//"),
+    block_comments: true,
+    quotes: &['"', '\''],
+    multiline_strings: false,
+    triple_quotes: false,
+    rust_literals: false,
+};
+
+const HASH: Syntax = Syntax {
+    line_comment: Some("#"),
+    block_comments: false,
+    quotes: &['"', '\''],
+    multiline_strings: false,
+    triple_quotes: false,
+    rust_literals: false,
+};
+
+fn syntax_for(path: &Path) -> Option<Syntax> {
+    let syntax = match language_for_path(path)? {
+        "rust" => Syntax {
+            quotes: &['"'],
+            multiline_strings: true,
+            rust_literals: true,
+            ..C_LIKE
+        },
+        "javascript" | "typescript" | "jsx" | "tsx" | "go" => Syntax {
+            quotes: &['"', '\'', '`'],
+            ..C_LIKE
+        },
+        "c" | "cpp" | "csharp" | "java" | "kotlin" | "scala" | "swift" | "dart" | "php"
+        | "protobuf" | "scss" => C_LIKE,
+        "css" => Syntax {
+            line_comment: None,
+            ..C_LIKE
+        },
+        "python" => Syntax {
+            triple_quotes: true,
+            ..HASH
+        },
+        "bash" | "zsh" | "fish" | "powershell" | "ruby" | "elixir" | "r" | "makefile"
+        | "dockerfile" => Syntax {
+            multiline_strings: true,
+            ..HASH
+        },
+        "yaml" | "toml" => HASH,
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+/// Remove comments from `content`, choosing the comment syntax from the extension of
+/// `path`: `//` and `/* */` for C-like languages, `#` for Python, shell, YAML and the like.
+/// String literals are left alone, lines that only held a comment are dropped and a
+/// leading `#!` line is kept. Returns `None` for languages without a known syntax.
+pub fn strip_comments(content: &str, path: &Path) -> Option<String> {
+    let syntax = syntax_for(path)?;
+    let chars: Vec<char> = content.chars().collect();
+    let mut out = String::with_capacity(content.len());
+    let mut i = 0;
+
+    // Keep the shebang, it's not just a comment
+    if syntax.line_comment == Some("#") && content.starts_with("#!") {
+        i = chars.iter().position(|&c| c == '\n').unwrap_or(chars.len());
+        out.extend(&chars[..i]);
+    }
+
+    while i < chars.len() {
+        let c = chars[i];
+        let rest = &chars[i..];
+
+        // Rust raw strings, r"..." and r#"..."#, which don't use escapes
+        if syntax.rust_literals && c == 'r' && (i == 0 || !is_ident(chars[i - 1])) {
+            let hashes = rest[1..].iter().take_while(|&&h| h == '#').count();
+            if rest.get(1 + hashes) == Some(&'"') {
+                let terminator: Vec<char> = std::iter::once('"')
+                    .chain(std::iter::repeat_n('#', hashes))
+                    .collect();
+                let end = find(&chars, i + 2 + hashes, &terminator)
+                    .map_or(chars.len(), |e| e + terminator.len());
+                out.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+        }
+
+        // Rust char literals ('a', '\n', '\u{1F600}'); anything else after a quote is a lifetime
+        if syntax.rust_literals && c == '\'' {
+            let len = if rest.get(1) == Some(&'\\') {
+                rest[2..].iter().position(|&c| c == '\'').map(|p| p + 3)
+            } else if rest.get(2) == Some(&'\'') {
+                Some(3)
+            } else {
+                None
+            };
+            let end = i + len.unwrap_or(1);
+            out.extend(&chars[i..end]);
+            i = end;
+            continue;
+        }
+
+        if syntax.triple_quotes && (c == '"' || c == '\'') && rest.starts_with(&[c, c, c]) {
+            let end = find(&chars, i + 3, &[c, c, c]).map_or(chars.len(), |e| e + 3);
+            out.extend(&chars[i..end]);
+            i = end;
+            continue;
+        }
+
+        if syntax.quotes.contains(&c) {
+            let mut end = i + 1;
+            while end < chars.len() {
+                match chars[end] {
+                    '\\' => end += 2,
+                    ch if ch == c => {
+                        end += 1;
+                        break;
+                    }
+                    // An unterminated string ends at the line break
+                    '\n' if !syntax.multiline_strings && c != '`' => break,
+                    _ => end += 1,
+                }
+            }
+            let end = end.min(chars.len());
+            out.extend(&chars[i..end]);
+            i = end;
+            continue;
+        }
+
+        if syntax.block_comments && rest.starts_with(&['/', '*']) {
+            let end = find(&chars, i + 2, &['*', '/']).map_or(chars.len(), |e| e + 2);
+            // Keep the line breaks so lines still line up with the original below
+            out.extend(chars[i..end].iter().filter(|&&c| c == '\n'));
+            i = end;
+            continue;
+        }
+
+        if let Some(marker) = syntax.line_comment {
+            let marker: Vec<char> = marker.chars().collect();
+            let at_word_start = marker != ['#'] || i == 0 || chars[i - 1].is_whitespace();
+            if at_word_start && rest.starts_with(&marker) {
+                i += rest.iter().position(|&c| c == '\n').unwrap_or(rest.len());
+                continue;
+            }
+        }
+
+        out.push(c);
+        i += 1;
+    }
+
+    // Drop lines that only held comments, and the whitespace comments left behind
+    let mut stripped = content
+        .lines()
+        .zip(out.lines())
+        .filter(|(original, line)| original.trim().is_empty() || !line.trim().is_empty())
+        .map(|(original, line)| {
+            if line.len() == original.len() {
+                line
+            } else {
+                line.trim_end()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if content.ends_with('\n') {
+        stripped.push('\n');
+    }
+    Some(stripped)
+}
+
+/// Replace each run of blank (or whitespace-only) lines with a single empty line
+pub fn collapse_blank_lines(content: &str) -> String {
+    let mut lines = Vec::new();
+    let mut previous_blank = false;
+    for line in content.lines() {
+        let blank = line.trim().is_empty();
+        if !(blank && previous_blank) {
+            lines.push(if blank { "" } else { line });
+        }
+        previous_blank = blank;
+    }
+    let mut collapsed = lines.join("\n");
+    if content.ends_with('\n') {
+        collapsed.push('\n');
+    }
+    collapsed
+}
+
+/// Cut every line longer than `max` characters down to `max` of them, followed by a
+/// "…[truncated N chars]" marker with the number of characters cut. Returns None when no
+/// line is that long.
+pub fn truncate_long_lines(content: &str, max: usize) -> Option<String> {
+    // A CR of a CRLF line ending isn't part of the line
+    let line_of = |line: &str| line.strip_suffix('\r').unwrap_or(line).chars().count();
+    if !content.split('\n').any(|line| line_of(line) > max) {
+        return None;
+    }
+    let lines = content
+        .split('\n')
+        .map(|line| {
+            let len = line_of(line);
+            if len <= max {
+                return line.to_string();
+            }
+            let cut = line.char_indices().nth(max).map_or(line.len(), |(i, _)| i);
+            let cr = if line.ends_with('\r') { "\r" } else { "" };
+            format!("{}…[truncated {} chars]{}", &line[..cut], len - max, cr)
+        })
+        .collect::<Vec<_>>();
+    Some(lines.join("\n"))
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Index of the first occurrence of `needle` in `chars` at or after `from`
+fn find(chars: &[char], from: usize, needle: &[char]) -> Option<usize> {
+    chars
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|p| p + from)
+}
@@ -0,0 +1,105 @@
+use crate::{config::YekConfig, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+use tracing::debug;
+
+/// How long the input directories must stay quiet after a change before serializing again
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Run `run` once, then again each time a burst of changes to files in the input
+/// directories that aren't ignored has settled. Changes inside the output directory are
+/// skipped, so writing the output doesn't trigger another run. An error from the first
+/// run is returned, later ones are printed as warnings and watching goes on. Only returns
+/// if the watcher fails to start or stops.
+pub fn watch_input_dirs(config: &YekConfig, mut run: impl FnMut() -> Result<()>) -> Result<()> {
+    let filter = ChangeFilter::new(config)?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for (root, _) in &filter.roots {
+        watcher.watch(root, RecursiveMode::Recursive)?;
+    }
+
+    // Watching starts first so changes made during the first run aren't missed
+    run()?;
+
+    // The sender lives in the watcher, so the channel only closes if the watcher goes away
+    while let Ok(event) = rx.recv() {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                debug!("Watch error: {}", e);
+                continue;
+            }
+        };
+        if !filter.is_relevant(&event) {
+            continue;
+        }
+
+        // Let the burst of changes (e.g. a save or a checkout) settle first
+        loop {
+            match rx.recv_timeout(DEBOUNCE) {
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+
+        debug!("Change in {:?}, serializing again", event.paths);
+        if let Err(e) = run() {
+            eprintln!("Warning: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Decides which file system events should trigger another run
+struct ChangeFilter {
+    /// Canonical input directories, with their ignore patterns
+    roots: Vec<(PathBuf, Gitignore)>,
+    output_dir: Option<PathBuf>,
+}
+
+impl ChangeFilter {
+    fn new(config: &YekConfig) -> Result<Self> {
+        let mut roots = Vec::new();
+        for dir in &config.input_dirs {
+            let root = Path::new(dir).canonicalize()?;
+            let mut builder = GitignoreBuilder::new(&root);
+            for pattern in &config.ignore_patterns {
+                builder.add_line(None, pattern)?;
+            }
+            roots.push((root, builder.build()?));
+        }
+        let output_dir = config
+            .output_dir
+            .as_deref()
+            .and_then(|dir| Path::new(dir).canonicalize().ok());
+        Ok(ChangeFilter { roots, output_dir })
+    }
+
+    fn is_relevant(&self, event: &Event) -> bool {
+        // Reading files, including our own reads of the input, changes nothing
+        if matches!(event.kind, EventKind::Access(_)) {
+            return false;
+        }
+        event.paths.iter().any(|path| self.is_relevant_path(path))
+    }
+
+    fn is_relevant_path(&self, path: &Path) -> bool {
+        if let Some(output_dir) = &self.output_dir {
+            if path.starts_with(output_dir) {
+                return false;
+            }
+        }
+        self.roots.iter().any(|(root, ignore)| {
+            path.starts_with(root)
+                && path != root
+                && !ignore.matched_path_or_any_parents(path, false).is_ignore()
+        })
+    }
+}
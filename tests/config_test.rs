@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
@@ -6,8 +7,8 @@ use tempfile::TempDir;
 use yek::defaults::{BINARY_FILE_EXTENSIONS, DEFAULT_IGNORE_PATTERNS, DEFAULT_OUTPUT_TEMPLATE};
 
 use yek::config::YekConfig;
-use yek::is_text_file;
-use yek::priority::PriorityRule;
+use yek::priority::{PriorityRule, PriorityTier};
+use yek::{is_text_file, parse_size_input};
 
 #[test]
 fn test_validate_config_valid() {
@@ -17,6 +18,7 @@ fn test_validate_config_valid() {
     config.priority_rules = vec![PriorityRule {
         pattern: ".*".to_string(),
         score: 10,
+        ..Default::default()
     }];
     config.binary_extensions = vec!["bin".to_string()];
 
@@ -41,13 +43,14 @@ fn test_validate_config_invalid_priority_rule_score() {
     config.priority_rules = vec![PriorityRule {
         pattern: "foo".to_string(),
         score: 1001,
+        ..Default::default()
     }];
 
     let result = config.validate();
     assert!(result.is_err());
     let err = result.unwrap_err().to_string();
     assert!(err.contains("priority_rules"));
-    assert!(err.contains("Priority score 1001 must be between 0 and 1000"));
+    assert!(err.contains("Priority score 1001 must be between -1000 and 1000"));
 }
 
 #[test]
@@ -56,6 +59,7 @@ fn test_validate_config_invalid_priority_rule_pattern() {
     config.priority_rules = vec![PriorityRule {
         pattern: "[".to_string(), // Invalid regex
         score: 100,
+        ..Default::default()
     }];
 
     let result = config.validate();
@@ -65,6 +69,23 @@ fn test_validate_config_invalid_priority_rule_pattern() {
     assert!(err.contains("Invalid pattern"));
 }
 
+#[test]
+fn test_validate_config_invalid_priority_rule_exclude_pattern() {
+    let mut config = YekConfig::extend_config_with_defaults(vec![], "/tmp/yek".to_string());
+    config.priority_rules = vec![PriorityRule {
+        pattern: "^src/".to_string(),
+        score: 100,
+        exclude_pattern: Some("(".to_string()), // Invalid regex
+        ..Default::default()
+    }];
+
+    let result = config.validate();
+    assert!(result.is_err());
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("priority_rules"));
+    assert!(err.contains("Invalid exclude_pattern '('"));
+}
+
 #[test]
 fn test_validate_config_invalid_ignore_pattern() {
     let mut config = YekConfig::extend_config_with_defaults(vec![], "/tmp/yek".to_string());
@@ -165,27 +186,38 @@ fn test_validate_invalid_priority_rules() {
     cfg.priority_rules.push(PriorityRule {
         pattern: "*.rs".to_string(),
         score: 500,
+        ..Default::default()
     });
     let result = cfg.validate();
     assert!(result.is_ok());
 
-    // Test 2: Invalid score
+    // Test 2: Negative scores are valid down to -1000
+    let mut cfg = YekConfig::default();
+    cfg.priority_rules.push(PriorityRule {
+        pattern: "*.lock".to_string(),
+        score: -1000,
+        ..Default::default()
+    });
+    assert!(cfg.validate().is_ok());
+
     let mut cfg = YekConfig::default();
     cfg.priority_rules.push(PriorityRule {
         pattern: "*.rs".to_string(),
-        score: -10,
+        score: -1001,
+        ..Default::default()
     });
     let result = cfg.validate();
     assert!(result.is_err());
     let err = result.unwrap_err().to_string();
     println!("Actual error message: {}", err);
-    assert!(err.contains("Priority score -10 must be between 0 and 1000"));
+    assert!(err.contains("Priority score -1001 must be between -1000 and 1000"));
 
     // Test 3: Invalid pattern
     let mut cfg = YekConfig::default();
     cfg.priority_rules.push(PriorityRule {
         pattern: "[[[".to_string(),
         score: 500,
+        ..Default::default()
     });
     let result = cfg.validate();
     assert!(result.is_err());
@@ -359,7 +391,7 @@ fn test_extend_config_with_defaults() {
     assert!(!cfg.stream);
     assert!(!cfg.token_mode);
     assert_eq!(cfg.output_file_full_path, None);
-    assert_eq!(cfg.max_git_depth, 100);
+    assert_eq!(cfg.max_git_depth, Some(100));
 }
 
 #[test]
@@ -380,6 +412,7 @@ fn test_validate_valid_config() {
     cfg.priority_rules.push(PriorityRule {
         pattern: "*.rs".to_string(),
         score: 500,
+        ..Default::default()
     });
 
     // Valid binary extensions
@@ -389,7 +422,7 @@ fn test_validate_valid_config() {
     cfg.git_boost_max = Some(500);
 
     // Valid max_git_depth
-    cfg.max_git_depth = 200;
+    cfg.max_git_depth = Some(200);
 
     let result = cfg.validate();
     assert!(result.is_ok());
@@ -563,3 +596,766 @@ fn test_is_text_file_with_binary_content() {
         "Expected a binary file to be detected as binary"
     );
 }
+
+#[test]
+fn test_validate_unknown_tokenizer() {
+    let cfg = YekConfig {
+        tokenizer: "gpt2_magic".to_string(),
+        ..YekConfig::default()
+    };
+    let err = cfg.validate().unwrap_err().to_string();
+    assert!(err.starts_with("tokenizer: Unknown tokenizer 'gpt2_magic'"));
+
+    let cfg = YekConfig {
+        tokenizer: "o200k_base".to_string(),
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+}
+
+#[test]
+fn test_validate_max_files_zero() {
+    let cfg = YekConfig {
+        max_files: Some(0),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "max_files: cannot be 0"
+    );
+}
+
+#[test]
+fn test_validate_binary_detection_settings() {
+    let cfg = YekConfig {
+        binary_scan_bytes: Some(0),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "binary_scan_bytes: cannot be 0"
+    );
+
+    let cfg = YekConfig {
+        null_byte_tolerance: Some(1.5),
+        ..YekConfig::default()
+    };
+    assert!(cfg
+        .validate()
+        .unwrap_err()
+        .to_string()
+        .contains("null_byte_tolerance"));
+}
+
+#[test]
+fn test_validate_git_frequency_weight_range() {
+    let cfg = YekConfig {
+        git_frequency_weight: 1.5,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "git_frequency_weight: 1.5 must be between 0.0 and 1.0"
+    );
+
+    let cfg = YekConfig {
+        git_frequency_weight: 0.5,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+}
+
+#[test]
+fn test_validate_git_boost_max_range() {
+    for boost in [-1, 1001] {
+        let cfg = YekConfig {
+            git_boost_max: Some(boost),
+            ..YekConfig::default()
+        };
+        assert_eq!(
+            cfg.validate().unwrap_err().to_string(),
+            format!("git_boost_max: {} must be between 0 and 1000", boost)
+        );
+    }
+}
+
+#[test]
+fn test_validate_unknown_compression() {
+    let cfg = YekConfig {
+        compress: Some("zip".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "compress: Unknown compression 'zip'. Expected one of: gzip"
+    );
+}
+
+#[test]
+fn test_validate_unknown_split_by() {
+    let cfg = YekConfig {
+        split_by: Some("size".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "split_by: Unknown split 'size'. Expected one of: directory"
+    );
+}
+
+#[test]
+fn test_validate_output_name_template() {
+    let mut cfg = YekConfig {
+        output_name_template: Some("{repo}-{date}-{checksum}.{ext}".to_string()),
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+
+    cfg.output_name_template = Some("{repo}-{branch}.{ext}".to_string());
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "output_name_template: Unknown placeholder '{branch}'. \
+         Expected one of: {checksum}, {repo}, {date}, {ext}, {group}"
+    );
+
+    cfg.output_name_template = Some("{repo}.{ext}".to_string());
+    cfg.split_by = Some("directory".to_string());
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "output_name_template: must contain {group} when split_by is set"
+    );
+}
+
+#[test]
+fn test_validate_encoding() {
+    let mut cfg = YekConfig::default();
+    for encoding in ["auto", "utf-8", "latin1", "UTF-16", "windows-1252"] {
+        cfg.encoding = Some(encoding.to_string());
+        assert!(cfg.validate().is_ok(), "{encoding} should be accepted");
+    }
+    cfg.encoding = Some("klingon".to_string());
+    assert!(cfg
+        .validate()
+        .unwrap_err()
+        .to_string()
+        .starts_with("encoding: Unknown encoding 'klingon'"));
+}
+
+#[test]
+fn test_validate_sort_by() {
+    let cfg = YekConfig {
+        sort_by: "mtime".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "sort_by: Unknown sort 'mtime'. Expected one of: priority, path, size"
+    );
+}
+
+#[test]
+fn test_validate_priority_combine() {
+    let cfg = YekConfig {
+        priority_combine: "avg".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "priority_combine: Unknown mode 'avg'. Expected one of: sum, max"
+    );
+}
+
+#[test]
+fn test_validate_output_dir_name() {
+    for name in ["", "..", "out/put"] {
+        let cfg = YekConfig {
+            output_dir_name: name.to_string(),
+            ..YekConfig::default()
+        };
+        assert_eq!(
+            cfg.validate().unwrap_err().to_string(),
+            format!("output_dir_name: '{}' must be a plain directory name", name)
+        );
+    }
+}
+
+#[test]
+fn test_output_ignore_patterns() {
+    let input = TempDir::new().unwrap();
+    fs::create_dir_all(input.path().join("build/yek")).unwrap();
+    let mut cfg = YekConfig {
+        input_dirs: vec![input.path().to_string_lossy().to_string()],
+        output_dir_name: "context".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(cfg.output_ignore_patterns(), vec!["context/"]);
+
+    cfg.output_dir = Some(input.path().join("build/yek").to_string_lossy().to_string());
+    assert_eq!(
+        cfg.output_ignore_patterns(),
+        vec!["context/", "/build/yek/"]
+    );
+
+    // An output directory outside the input needs no pattern
+    let elsewhere = TempDir::new().unwrap();
+    cfg.output_dir = Some(elsewhere.path().to_string_lossy().to_string());
+    assert_eq!(cfg.output_ignore_patterns(), vec!["context/"]);
+}
+
+#[test]
+fn test_validate_priority_rule_syntax() {
+    let cfg = YekConfig {
+        priority_rule_syntax: "pcre".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "priority_rule_syntax: Unknown syntax 'pcre'. Expected one of: regex, glob"
+    );
+
+    let mut cfg = YekConfig {
+        priority_rules: vec![PriorityRule {
+            pattern: "src/**/*.rs".to_string(),
+            score: 10,
+            syntax: Some("wildcard".to_string()),
+            ..Default::default()
+        }],
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "priority_rules: Unknown syntax 'wildcard' for pattern 'src/**/*.rs'. Expected one of: regex, glob"
+    );
+
+    cfg.priority_rules[0].syntax = None;
+    cfg.priority_rule_syntax = "glob".to_string();
+    assert!(cfg.validate().is_ok());
+    cfg.priority_rules[0].pattern = "src/{a,b".to_string();
+    assert!(cfg
+        .validate()
+        .unwrap_err()
+        .to_string()
+        .starts_with("priority_rules: Invalid glob 'src/{a,b': "));
+}
+
+#[test]
+fn test_validate_max_file_size() {
+    let cfg = YekConfig {
+        max_file_size: Some("lots".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "max_file_size: Invalid size format: 'lots'"
+    );
+
+    let cfg = YekConfig {
+        max_file_size: Some("0".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "max_file_size: cannot be 0"
+    );
+}
+
+#[test]
+fn test_validate_max_total_size() {
+    let cfg = YekConfig {
+        max_total_size: Some("lots".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "max_total_size: Invalid size format: 'lots'"
+    );
+
+    let cfg = YekConfig {
+        max_total_size: Some("0".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "max_total_size: cannot be 0"
+    );
+}
+
+#[test]
+fn test_warnings_stream_with_output_dir() {
+    let mut cfg = YekConfig {
+        stream: true,
+        output_dir: Some("out".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.warnings(),
+        vec!["stream: output_dir 'out' is not used when streaming to stdout"]
+    );
+
+    // Incremental mode keeps its cache in output_dir
+    cfg.incremental = true;
+    assert!(cfg.warnings().is_empty());
+
+    cfg.incremental = false;
+    cfg.stream = false;
+    assert!(cfg.warnings().is_empty());
+}
+
+#[test]
+fn test_warnings_max_size_without_unit() {
+    let mut cfg = YekConfig {
+        max_size: "100".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.warnings(),
+        vec!["max_size: '100' has no unit and is read as bytes, e.g. use \"100KB\" for kilobytes"]
+    );
+
+    cfg.max_size = "100KB".to_string();
+    assert!(cfg.warnings().is_empty());
+
+    // A bare count is expected in token mode
+    cfg.max_size = "100".to_string();
+    cfg.token_mode = true;
+    assert!(cfg.warnings().is_empty());
+}
+
+#[test]
+fn test_validate_json_with_jsonl() {
+    let mut cfg = YekConfig {
+        jsonl: true,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+    cfg.json = true;
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "jsonl: can't be combined with json"
+    );
+}
+
+#[test]
+fn test_validate_paths_only_with_jsonl() {
+    let cfg = YekConfig {
+        tree: true,
+        jsonl: true,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "paths_only: can't be combined with jsonl"
+    );
+}
+
+#[test]
+fn test_validate_normalize_line_endings() {
+    let mut cfg = YekConfig {
+        normalize_line_endings: Some("crlf".to_string()),
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+    cfg.normalize_line_endings = Some("cr".to_string());
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "normalize_line_endings: Unknown line ending 'cr'. Expected one of: lf, crlf, none"
+    );
+}
+
+#[test]
+fn test_from_sources_binary_extensions_replace() {
+    let temp_dir = TempDir::new().unwrap();
+    let parsed = |replace: bool| YekConfig {
+        input_dirs: vec![temp_dir.path().to_string_lossy().to_string()],
+        binary_extensions: vec!["blend".to_string()],
+        binary_extensions_replace: replace,
+        ..YekConfig::default()
+    };
+    let resolve = |parsed: YekConfig| {
+        YekConfig::from_sources(parsed, None, &HashMap::new(), &["yek".to_string()], false).unwrap()
+    };
+
+    // By default the configured extensions extend the built-in list
+    let cfg = resolve(parsed(false));
+    assert!(cfg.binary_extensions.contains(&"blend".to_string()));
+    assert!(cfg.binary_extensions.contains(&"exe".to_string()));
+
+    // In replace mode a built-in extension the user didn't list is dropped
+    let cfg = resolve(parsed(true));
+    assert_eq!(cfg.binary_extensions, vec!["blend".to_string()]);
+}
+
+#[test]
+fn test_from_sources_defaults_priority_options() {
+    // Config-only options not in the config file come out of parsing empty
+    let temp_dir = TempDir::new().unwrap();
+    let parsed = YekConfig {
+        input_dirs: vec![temp_dir.path().to_string_lossy().to_string()],
+        priority_combine: String::new(),
+        priority_rule_syntax: String::new(),
+        ..YekConfig::default()
+    };
+    let cfg = YekConfig::from_sources(parsed, None, &HashMap::new(), &["yek".to_string()], false)
+        .unwrap();
+    assert_eq!(cfg.priority_combine, "sum");
+    assert_eq!(cfg.priority_rule_syntax, "regex");
+}
+
+#[test]
+fn test_from_sources_precedence() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dirs = vec![temp_dir.path().to_string_lossy().to_string()];
+    let env: HashMap<String, String> = [("YEK_MAX_SIZE".to_string(), "2KiB".to_string())].into();
+    let max_size = |parsed: YekConfig, env: &HashMap<String, String>, args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let cfg = YekConfig::from_sources(parsed, None, env, &args, false).unwrap();
+        parse_size_input(&cfg.max_size, false).unwrap()
+    };
+    let defaults = || YekConfig {
+        input_dirs: input_dirs.clone(),
+        ..YekConfig::default()
+    };
+    let from_file = || YekConfig {
+        max_size: "3KiB".to_string(),
+        ..defaults()
+    };
+
+    // Defaults, then the config file, then the environment, then the command line
+    assert_eq!(
+        max_size(defaults(), &HashMap::new(), &["yek"]),
+        10 * 1024 * 1024
+    );
+    assert_eq!(max_size(from_file(), &HashMap::new(), &["yek"]), 3 * 1024);
+    assert_eq!(max_size(from_file(), &env, &["yek"]), 2 * 1024);
+    let from_cli = YekConfig {
+        max_size: "4KiB".to_string(),
+        ..defaults()
+    };
+    assert_eq!(
+        max_size(from_cli, &env, &["yek", "--max-size", "4KiB"]),
+        4 * 1024
+    );
+
+    // The rest of the resolution happens too
+    let cfg = YekConfig::from_sources(
+        defaults(),
+        None,
+        &HashMap::new(),
+        &["yek".to_string()],
+        false,
+    )
+    .unwrap();
+    assert!(cfg
+        .ignore_patterns
+        .contains(&DEFAULT_IGNORE_PATTERNS[0].to_string()));
+    assert!(YekConfig::from_sources(
+        YekConfig {
+            max_files: Some(0),
+            ..defaults()
+        },
+        None,
+        &HashMap::new(),
+        &["yek".to_string()],
+        false
+    )
+    .is_err());
+}
+
+#[test]
+fn test_from_sources_has_no_side_effects() {
+    let temp_dir = TempDir::new().unwrap();
+    let output_dir = temp_dir.path().join("out");
+    let parsed = || YekConfig {
+        input_dirs: vec![temp_dir.path().to_string_lossy().to_string()],
+        output_dir: Some(output_dir.to_string_lossy().to_string()),
+        ..YekConfig::default()
+    };
+    let resolve = |parsed: YekConfig, config_file: Option<&Path>, is_terminal: bool| {
+        YekConfig::from_sources(
+            parsed,
+            config_file,
+            &HashMap::new(),
+            &["yek".to_string()],
+            is_terminal,
+        )
+    };
+
+    // Output to a terminal goes to output_dir, which is resolved but left for the caller
+    // to create
+    let cfg = resolve(parsed(), None, true).unwrap();
+    assert!(!cfg.stream);
+    assert_eq!(cfg.output_dir, parsed().output_dir);
+    assert!(!output_dir.exists());
+    assert!(resolve(parsed(), None, false).unwrap().stream);
+
+    // With strict_config, the given config file has to parse
+    let config_file = temp_dir.path().join("yek.toml");
+    fs::write(&config_file, "max_size = 5\n").unwrap();
+    assert!(resolve(parsed(), Some(&config_file), true).is_ok());
+    let strict = YekConfig {
+        strict_config: true,
+        ..parsed()
+    };
+    let err = resolve(strict, Some(&config_file), true).unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("strict_config: invalid config file"));
+}
+
+#[test]
+fn test_apply_env_overrides() {
+    let env = |vars: &[(&str, &str)]| -> HashMap<String, String> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+
+    // Without a flag on the command line the variable replaces the config file value
+    let mut cfg = YekConfig::default();
+    cfg.apply_env_overrides(&env(&[("YEK_MAX_SIZE", "1KiB")]), &["yek".to_string()])
+        .unwrap();
+    assert_eq!(parse_size_input(&cfg.max_size, false).unwrap(), 1024);
+
+    // A flag on the command line wins over the variable
+    let mut cfg = YekConfig {
+        max_size: "2KB".to_string(),
+        ..YekConfig::default()
+    };
+    let args = ["yek".to_string(), "--max-size=2KB".to_string()];
+    cfg.apply_env_overrides(&env(&[("YEK_MAX_SIZE", "1KB")]), &args)
+        .unwrap();
+    assert_eq!(cfg.max_size, "2KB");
+
+    let mut cfg = YekConfig::default();
+    cfg.apply_env_overrides(
+        &env(&[
+            ("YEK_TOKENS", "128k"),
+            ("YEK_OUTPUT_DIR", "/tmp/yek-env"),
+            ("YEK_STREAM", "true"),
+        ]),
+        &[],
+    )
+    .unwrap();
+    assert!(cfg.token_mode);
+    assert_eq!(cfg.tokens, "128k");
+    assert_eq!(cfg.output_dir.as_deref(), Some("/tmp/yek-env"));
+    assert!(cfg.stream);
+
+    // --stream on the command line wins over YEK_STREAM
+    let mut cfg = YekConfig {
+        stream: true,
+        ..YekConfig::default()
+    };
+    cfg.apply_env_overrides(
+        &env(&[("YEK_STREAM", "0")]),
+        &["yek".to_string(), "--stream".to_string()],
+    )
+    .unwrap();
+    assert!(cfg.stream);
+
+    let err = YekConfig::default()
+        .apply_env_overrides(&env(&[("YEK_STREAM", "maybe")]), &[])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "YEK_STREAM: expected true or false, got 'maybe'"
+    );
+    let err = YekConfig::default()
+        .apply_env_overrides(&env(&[("YEK_MAX_SIZE", "lots")]), &[])
+        .unwrap_err();
+    assert_eq!(err.to_string(), "YEK_MAX_SIZE: Invalid size format: 'lots'");
+}
+
+#[test]
+fn test_apply_model_preset() {
+    let mut cfg = YekConfig {
+        model: Some("gpt-4o".to_string()),
+        ..YekConfig::default()
+    };
+    cfg.apply_model_preset().unwrap();
+    assert!(cfg.token_mode);
+    assert_eq!(cfg.tokenizer, "o200k_base");
+    assert_eq!(parse_size_input(&cfg.tokens, true).unwrap(), 128_000);
+    assert!(cfg.validate().is_ok());
+
+    // Settings made by the user are kept
+    let mut cfg = YekConfig {
+        model: Some("GPT-4o".to_string()),
+        tokens: "32k".to_string(),
+        tokenizer: "whitespace".to_string(),
+        ..YekConfig::default()
+    };
+    cfg.apply_model_preset().unwrap();
+    assert!(cfg.token_mode);
+    assert_eq!(cfg.tokens, "32k");
+    assert_eq!(cfg.tokenizer, "whitespace");
+
+    let mut cfg = YekConfig {
+        model: Some("gpt-99".to_string()),
+        ..YekConfig::default()
+    };
+    assert!(cfg
+        .apply_model_preset()
+        .unwrap_err()
+        .to_string()
+        .starts_with("model: Unknown model 'gpt-99'. Expected one of: gpt-4o, "));
+    assert!(!cfg.token_mode);
+}
+
+#[test]
+fn test_apply_model_preset_percentage() {
+    let mut cfg = YekConfig {
+        model: Some("gpt-4o".to_string()),
+        max_size: "50%".to_string(),
+        ..YekConfig::default()
+    };
+    cfg.apply_model_preset().unwrap();
+    assert!(cfg.token_mode);
+    assert_eq!(parse_size_input(&cfg.tokens, true).unwrap(), 64_000);
+    assert!(cfg.validate().is_ok());
+
+    let mut cfg = YekConfig {
+        model: Some("gpt-4o".to_string()),
+        tokens: "80%".to_string(),
+        ..YekConfig::default()
+    };
+    cfg.apply_model_preset().unwrap();
+    assert_eq!(parse_size_input(&cfg.tokens, true).unwrap(), 102_400);
+
+    let mut cfg = YekConfig {
+        model: Some("gpt-4o".to_string()),
+        tokens: "150%".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.apply_model_preset().unwrap_err().to_string(),
+        "tokens: Invalid percentage '150%'. Expected a number above 0 and up to 100, e.g. \"80%\""
+    );
+
+    // Without a model there is no window to take a percentage of
+    let cfg = YekConfig {
+        max_size: "50%".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "max_size: '50%' is a percentage of a model's context window, so it needs --model"
+    );
+}
+
+#[test]
+fn test_validate_depth_priority() {
+    let cfg = YekConfig {
+        depth_priority: 2000,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "depth_priority: 2000 must be between -1000 and 1000"
+    );
+}
+
+#[test]
+fn test_validate_single_document_with_split_by() {
+    let cfg = YekConfig {
+        single_document: true,
+        split_by: Some("directory".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "single_document: can't be combined with split_by"
+    );
+}
+
+#[test]
+fn test_validate_absolute_paths_with_path_prefix() {
+    let cfg = YekConfig {
+        absolute_paths: true,
+        path_prefix: Some("proj/".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "absolute_paths: can't be combined with path_prefix"
+    );
+}
+
+#[test]
+fn test_validate_priority_tiers() {
+    let tier = |name: &str, min_priority: i32| PriorityTier {
+        name: name.to_string(),
+        min_priority,
+    };
+    let cfg = YekConfig {
+        tier_headers: true,
+        priority_tiers: vec![tier("HIGH", 100), tier("ALSO HIGH", 100)],
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "priority_tiers: two tiers start at priority 100"
+    );
+
+    let cfg = YekConfig {
+        priority_tiers: vec![tier("HIGH", 100)],
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+    assert_eq!(
+        cfg.warnings(),
+        vec!["priority_tiers: not used unless tier_headers is set".to_string()]
+    );
+}
+
+#[test]
+fn test_validate_prompt_file() {
+    let cfg = YekConfig {
+        prompt_file: Some("/nonexistent/prompt.md".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "prompt_file: /nonexistent/prompt.md is not a file"
+    );
+
+    let temp_dir = TempDir::new().unwrap();
+    let prompt_file = temp_dir.path().join("prompt.md");
+    fs::write(&prompt_file, "Review this code.").unwrap();
+    let cfg = YekConfig {
+        prompt_file: Some(prompt_file.to_string_lossy().to_string()),
+        json: true,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "prompt_file: can't be combined with json or jsonl"
+    );
+}
+
+#[test]
+fn test_validate_tree_header_with_json() {
+    let cfg = YekConfig {
+        tree_header: true,
+        json: true,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "tree_header: can't be combined with json or jsonl"
+    );
+}
+
+#[test]
+fn test_validate_skip_if_unchanged_with_split_by() {
+    let mut cfg = YekConfig {
+        skip_if_unchanged: true,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+    cfg.split_by = Some("directory".to_string());
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "skip_if_unchanged: can't be used with split_by"
+    );
+}
@@ -1,8 +1,11 @@
 #[cfg(test)]
 mod e2e_tests {
     use assert_cmd::Command;
+    use flate2::read::GzDecoder;
     use predicates::prelude::*;
     use std::fs;
+    use std::io::Read;
+    use std::path::Path;
 
     use tempfile::tempdir;
 
@@ -26,6 +29,25 @@ mod e2e_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_runs_without_config_file() -> Result<(), Box<dyn std::error::Error>> {
+        // Config-only options must fall back to their defaults, not to zero values
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("test.txt"), "Test content")?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(".")
+            .output()?;
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(String::from_utf8(output.stdout)?.contains("Test content"));
+        Ok(())
+    }
+
     #[test]
     fn test_multiple_files() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = tempdir()?;
@@ -54,6 +76,138 @@ mod e2e_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_default_ignores() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::create_dir_all(temp_dir.path().join("node_modules/left-pad"))?;
+        fs::write(
+            temp_dir.path().join("node_modules/left-pad/index.js"),
+            "module.exports = pad;",
+        )?;
+        fs::write(temp_dir.path().join("main.js"), "require('left-pad');")?;
+
+        let output = Command::cargo_bin("yek")?.arg(temp_dir.path()).output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(stdout.contains("main.js"));
+        assert!(!stdout.contains("node_modules/left-pad/index.js"));
+
+        let output = Command::cargo_bin("yek")?
+            .arg(temp_dir.path())
+            .arg("--no-default-ignores")
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(stdout.contains("main.js"));
+        assert!(stdout.contains("node_modules/left-pad/index.js"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_binary() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(
+            temp_dir.path().join("logo.png"),
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', 0, 0],
+        )?;
+        fs::write(temp_dir.path().join("data.txt"), "ab\0\0\0\0cd")?;
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}")?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(temp_dir.path())
+            .arg("--list-binary")
+            .output()?;
+        assert!(output.status.success());
+        assert!(String::from_utf8(output.stdout)?.contains("main.rs"));
+        assert_eq!(
+            String::from_utf8(output.stderr)?,
+            "2 files skipped as binary\n\
+             Binary extension:\n  logo.png\n\
+             Binary content (null bytes or a binary signature):\n  data.txt\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_regenerates_output() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::{BufRead, BufReader};
+        use std::process::Stdio;
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}")?;
+        // Inside the watched directory, so writing the output must not trigger another run
+        let output_dir = temp_dir.path().join("out");
+
+        let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("yek"))
+            .env("FORCE_TTY", "1")
+            .arg(temp_dir.path())
+            .arg("--output-dir")
+            .arg(&output_dir)
+            .arg("--watch")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        // Each run prints the path of the output file it wrote
+        let stdout = child.stdout.take().unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                tx.send(line).ok();
+            }
+        });
+        let timeout = Duration::from_secs(20);
+
+        let first = rx.recv_timeout(timeout)?;
+        assert!(fs::read_to_string(&first)?.contains("fn main() {}"));
+
+        fs::write(temp_dir.path().join("main.rs"), "fn main() { watch(); }")?;
+        let second = rx.recv_timeout(timeout)?;
+        assert!(fs::read_to_string(&second)?.contains("fn main() { watch(); }"));
+
+        // Nothing changed since, so there is no further run
+        let further = rx.recv_timeout(Duration::from_secs(2));
+        child.kill()?;
+        child.wait()?;
+        assert!(further.is_err(), "unexpected run: {:?}", further);
+        Ok(())
+    }
+
+    #[test]
+    fn test_previous_output_is_not_serialized() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}")?;
+
+        // The default output directory lands inside the input when it is the temp dir
+        let run = |args: &[&str]| -> Result<String, Box<dyn std::error::Error>> {
+            let output = Command::cargo_bin("yek")?
+                .current_dir(temp_dir.path())
+                .env("FORCE_TTY", "1")
+                .env("TMPDIR", temp_dir.path())
+                .arg(temp_dir.path())
+                .args(args)
+                .output()?;
+            assert!(output.status.success());
+            let path = String::from_utf8(output.stdout)?;
+            Ok(path.trim().to_string())
+        };
+
+        for args in [&[][..], &["--output-dir-name", "context"][..]] {
+            let first = run(args)?;
+            let second = run(args)?;
+            let dir_name = args.last().copied().unwrap_or("yek-output");
+            assert!(Path::new(&second).starts_with(temp_dir.path().join(dir_name)));
+            let output = fs::read_to_string(&second)?;
+            assert!(output.contains(">>>> main.rs"));
+            assert!(!output.contains(&format!("{}/", dir_name)), "{}", output);
+            assert_ne!(fs::read_to_string(&first)?, "");
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_priority_rules() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = tempdir()?;
@@ -78,6 +232,109 @@ mod e2e_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_print_binary_extensions() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let config_file = temp_dir.path().join("yek.toml");
+        fs::write(&config_file, "binary_extensions = [\"blend\", \"psd\"]\n")?;
+
+        let output = Command::cargo_bin("yek")?
+            .arg("--print-binary-extensions")
+            .arg("--config-file")
+            .arg(&config_file)
+            .arg(temp_dir.path())
+            .output()?;
+        assert!(output.status.success());
+        let extensions = String::from_utf8(output.stdout)?;
+        let extensions: Vec<&str> = extensions.lines().collect();
+        assert!(extensions.contains(&"blend"));
+        assert!(extensions.contains(&"exe"));
+        assert!(extensions.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        fs::write(
+            &config_file,
+            "binary_extensions = [\"blend\", \"psd\"]\nbinary_extensions_replace = true\n",
+        )?;
+        Command::cargo_bin("yek")?
+            .arg("--print-binary-extensions")
+            .arg("--config-file")
+            .arg(&config_file)
+            .arg(temp_dir.path())
+            .assert()
+            .success()
+            .stdout("blend\npsd\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_config() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("test.txt"), "Test content")?;
+        let config_file = temp_dir.path().join("yek.toml");
+
+        // Invalid configs fail with or without strict_config
+        fs::write(
+            &config_file,
+            "[[priority_rules]]\npattern = \"src/\"\nscore = 2000\n",
+        )?;
+        Command::cargo_bin("yek")?
+            .arg("--strict-config")
+            .arg("--config-file")
+            .arg(&config_file)
+            .arg(temp_dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "priority_rules: Priority score 2000 must be between -1000 and 1000",
+            ));
+
+        // Warnings only fail the run with strict_config
+        fs::write(
+            &config_file,
+            "[[priority_tiers]]\nname = \"CORE\"\nmin_priority = 100\n",
+        )?;
+        Command::cargo_bin("yek")?
+            .arg("--config-file")
+            .arg(&config_file)
+            .arg(temp_dir.path())
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("Warning: priority_tiers:"));
+        Command::cargo_bin("yek")?
+            .arg("--strict-config")
+            .arg("--config-file")
+            .arg(&config_file)
+            .arg(temp_dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "Error: strict_config: the config has 1 problem(s):\n  priority_tiers: not used unless tier_headers is set",
+            ));
+
+        // A config file that doesn't parse fails with strict_config instead of being
+        // replaced by the defaults
+        for broken in ["max_size = ", "max_size = 5\n", "max_sise = \"5KB\"\n"] {
+            fs::write(&config_file, broken)?;
+            Command::cargo_bin("yek")?
+                .arg("--config-file")
+                .arg(&config_file)
+                .arg(temp_dir.path())
+                .assert()
+                .success();
+            Command::cargo_bin("yek")?
+                .arg("--strict-config")
+                .arg("--config-file")
+                .arg(&config_file)
+                .arg(temp_dir.path())
+                .assert()
+                .failure()
+                .stderr(predicate::str::contains(
+                    "Error: strict_config: invalid config file",
+                ));
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_binary_files() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = tempdir()?;
@@ -88,6 +345,44 @@ mod e2e_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_manifest() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let repo = temp_dir.path().join("repo");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&repo)?;
+        fs::write(repo.join("main.rs"), "fn main() {}")?;
+        fs::write(repo.join("lib.rs"), "pub fn lib() {}")?;
+
+        let output = Command::cargo_bin("yek")?
+            .env("FORCE_TTY", "1")
+            .arg(&repo)
+            .arg("--output-dir")
+            .arg(&output_dir)
+            .arg("--manifest")
+            .output()?;
+        assert!(output.status.success());
+        let output_path = String::from_utf8(output.stdout)?.trim().to_string();
+        let content = fs::read_to_string(&output_path)?;
+
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output_dir.join("yek-manifest.json"))?)?;
+        assert_eq!(manifest["outputs"], serde_json::json!([output_path]));
+        let checksum = manifest["checksum"].as_str().unwrap();
+        assert!(output_path.ends_with(&format!("yek-output-{}.txt", checksum)));
+        assert_eq!(manifest["total_bytes"], content.len());
+        assert!(manifest["total_tokens"].as_u64().unwrap() > 0);
+
+        let files = manifest["files"].as_array().unwrap();
+        assert_eq!(files.len(), content.matches(">>>> ").count());
+        assert_eq!(
+            files[0],
+            serde_json::json!({"path": "lib.rs", "priority": 0, "chunk": 0})
+        );
+        assert_eq!(files[1]["path"], "main.rs");
+        Ok(())
+    }
+
     #[test]
     fn test_output_dir() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = tempdir()?;
@@ -146,6 +441,135 @@ mod e2e_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_gzip_compression() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}")?;
+        fs::write(temp_dir.path().join("lib.rs"), "pub fn lib() {}")?;
+        let output_dir = tempdir()?;
+
+        let run = |compress: bool, tty: bool| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+            let mut cmd = Command::cargo_bin("yek")?;
+            cmd.arg(temp_dir.path())
+                .arg("--output-dir")
+                .arg(output_dir.path());
+            if tty {
+                cmd.env("FORCE_TTY", "1");
+            }
+            if compress {
+                cmd.arg("--compress").arg("gzip");
+            }
+            let output = cmd.output()?;
+            assert!(output.status.success());
+            Ok(output.stdout)
+        };
+        let gunzip = |bytes: &[u8]| -> Result<String, Box<dyn std::error::Error>> {
+            let mut decoded = String::new();
+            GzDecoder::new(bytes).read_to_string(&mut decoded)?;
+            Ok(decoded)
+        };
+
+        // Output file
+        let plain_path = String::from_utf8(run(false, true)?)?;
+        let gz_path = String::from_utf8(run(true, true)?)?;
+        assert_eq!(gz_path.trim(), format!("{}.gz", plain_path.trim()));
+        let plain = fs::read_to_string(plain_path.trim())?;
+        assert!(plain.contains("fn main() {}"));
+        assert_eq!(gunzip(&fs::read(gz_path.trim())?)?, plain);
+
+        // Streaming
+        let plain = String::from_utf8(run(false, false)?)?;
+        assert_eq!(gunzip(&run(true, false)?)?, plain);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_by_directory() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::create_dir(temp_dir.path().join("src"))?;
+        fs::create_dir(temp_dir.path().join("docs"))?;
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}")?;
+        fs::write(temp_dir.path().join("src/lib.rs"), "pub fn lib() {}")?;
+        fs::write(temp_dir.path().join("docs/guide.md"), "# Guide")?;
+        let output_dir = tempdir()?;
+
+        let output = Command::cargo_bin("yek")?
+            .env("FORCE_TTY", "1")
+            .arg(temp_dir.path())
+            .arg("--output-dir")
+            .arg(output_dir.path())
+            .arg("--split-by")
+            .arg("directory")
+            .output()?;
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8(output.stdout)?;
+        let paths: Vec<&str> = stdout.lines().collect();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(fs::read_dir(output_dir.path())?.count(), 2);
+
+        let docs = paths.iter().find(|p| p.ends_with("-docs.txt")).unwrap();
+        let src = paths.iter().find(|p| p.ends_with("-src.txt")).unwrap();
+        assert_eq!(fs::read_to_string(docs)?, ">>>> docs/guide.md\n# Guide");
+        let src = fs::read_to_string(src)?;
+        assert!(src.contains(">>>> src/main.rs\nfn main() {}"));
+        assert!(src.contains(">>>> src/lib.rs\npub fn lib() {}"));
+        assert!(!src.contains("Guide"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_name_template() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let repo = temp_dir.path().join("my-repo");
+        fs::create_dir(&repo)?;
+        fs::write(repo.join("main.rs"), "fn main() {}")?;
+        let output_dir = tempdir()?;
+
+        let output = Command::cargo_bin("yek")?
+            .env("FORCE_TTY", "1")
+            .arg(&repo)
+            .arg("--output-dir")
+            .arg(output_dir.path())
+            .arg("--output-name-template")
+            .arg("{repo}-{date}.{ext}")
+            .arg("--compress")
+            .arg("gzip")
+            .output()?;
+        assert!(output.status.success());
+
+        let today = time::OffsetDateTime::now_utc().date();
+        let expected = output_dir.path().join(format!(
+            "my-repo-{}-{:02}-{:02}.txt.gz",
+            today.year(),
+            u8::from(today.month()),
+            today.day()
+        ));
+        let stdout = String::from_utf8(output.stdout)?;
+        assert_eq!(stdout.trim(), expected.to_string_lossy());
+        assert!(expected.exists());
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_clipboard_falls_back_to_stdout() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("test.rs"), "Test content")?;
+
+        // No display server, so there is no clipboard to copy to
+        let output = Command::cargo_bin("yek")?
+            .env_remove("DISPLAY")
+            .env_remove("WAYLAND_DISPLAY")
+            .arg(temp_dir.path())
+            .arg("--clipboard")
+            .output()?;
+        assert!(output.status.success());
+        assert!(String::from_utf8(output.stdout)?.contains(">>>> test.rs\nTest content"));
+        assert!(String::from_utf8(output.stderr)?.contains("Printing to stdout instead"));
+        Ok(())
+    }
+
     #[test]
     fn test_max_size() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = tempdir()?;
@@ -160,6 +584,80 @@ mod e2e_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_dropped_files_warning() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "a".repeat(600))?;
+        fs::write(temp_dir.path().join("b.txt"), "b".repeat(600))?;
+
+        // Warned about once, however many times the files are selected
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(temp_dir.path())
+            .args(["--max-size", "1KB", "--dry-run"])
+            .output()?;
+        assert!(output.status.success());
+        let stderr = String::from_utf8(output.stderr)?;
+        assert_eq!(
+            stderr
+                .matches("Warning: Dropped 1 lower-priority file(s)")
+                .count(),
+            1,
+            "{}",
+            stderr
+        );
+
+        Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(temp_dir.path())
+            .args(["--max-size", "1KB", "--quiet"])
+            .assert()
+            .success()
+            .stderr("");
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_total_size_across_chunks() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        for dir in ["a", "b", "c", "d", "e"] {
+            fs::create_dir(temp_dir.path().join(dir))?;
+            fs::write(temp_dir.path().join(dir).join("f.rs"), "x".repeat(100))?;
+        }
+        fs::write(
+            temp_dir.path().join("yek.toml"),
+            r#"
+            [[priority_rules]]
+            pattern = "^e/"
+            score = 50
+            [[priority_rules]]
+            pattern = "^d/"
+            score = 40
+            "#,
+        )?;
+        let output_dir = tempdir()?;
+
+        // Each entry is 112 bytes, so only the two highest-priority ones fit
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .env("FORCE_TTY", "1")
+            .arg(".")
+            .arg("--output-dir")
+            .arg(output_dir.path())
+            .args(["--split-by", "directory", "--max-total-size", "250B"])
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        let mut paths: Vec<&str> = stdout.lines().collect();
+        paths.sort();
+        assert_eq!(paths.len(), 2, "{stdout}");
+        assert!(paths[0].ends_with("-d.txt") && paths[1].ends_with("-e.txt"));
+        assert!(String::from_utf8(output.stderr)?.contains(
+            "Warning: Dropped 3 lower-priority file(s) to stay within the max_size and max_total_size limits"
+        ));
+        Ok(())
+    }
+
     #[test]
     fn test_tokens_mode() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = tempdir()?;
@@ -200,6 +698,205 @@ mod e2e_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_since_with_subdirectory_input() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let git = |args: &[&str], date: &str| {
+            std::process::Command::new("git")
+                .args(["-c", "user.name=yek", "-c", "user.email=yek@example.com"])
+                .args(args)
+                .env("GIT_AUTHOR_DATE", date)
+                .env("GIT_COMMITTER_DATE", date)
+                .current_dir(temp_dir.path())
+                .output()
+        };
+        git(&["init"], "")?;
+        fs::create_dir(temp_dir.path().join("sub"))?;
+        fs::write(temp_dir.path().join("sub/old.rs"), "old")?;
+        git(&["add", "."], "")?;
+        git(&["commit", "-m", "old"], "2001-01-01T00:00:00Z")?;
+        fs::write(temp_dir.path().join("sub/new.rs"), "new")?;
+        git(&["add", "."], "")?;
+        git(&["commit", "-m", "new"], "2021-01-01T00:00:00Z")?;
+        fs::write(temp_dir.path().join("sub/untracked.rs"), "untracked")?;
+
+        // Runs without a config file, on the repository and on a directory inside it
+        for (input, prefix) in [(".", "sub/"), ("sub", "")] {
+            let output = Command::cargo_bin("yek")?
+                .current_dir(temp_dir.path())
+                .args([input, "--since", "2010-01-01"])
+                .output()?;
+            assert!(output.status.success());
+            let stdout = String::from_utf8(output.stdout)?;
+            assert!(stdout.contains(&format!(">>>> {prefix}new.rs")), "{stdout}");
+            assert!(
+                stdout.contains(&format!(">>>> {prefix}untracked.rs")),
+                "{stdout}"
+            );
+            assert!(!stdout.contains("old.rs"), "{stdout}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_show_commit_without_config_file() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(["-c", "user.name=yek", "-c", "user.email=yek@example.com"])
+                .args(args)
+                .current_dir(temp_dir.path())
+                .output()
+        };
+        git(&["init"])?;
+        fs::write(temp_dir.path().join("committed.rs"), "committed")?;
+        git(&["add", "."])?;
+        git(&["commit", "-m", "initial"])?;
+        fs::write(temp_dir.path().join("untracked.rs"), "untracked")?;
+        let head = String::from_utf8(git(&["rev-parse", "--short=7", "HEAD"])?.stdout)?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg("--show-commit")
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(
+            stdout.contains(&format!(">>>> committed.rs @{}\n", head.trim())),
+            "{stdout}"
+        );
+        assert!(stdout.contains(">>>> untracked.rs\n"), "{stdout}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_repository_url() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let work = temp_dir.path().join("work");
+        let clone_tmp = temp_dir.path().join("tmp");
+        fs::create_dir_all(work.join("src"))?;
+        fs::create_dir(&clone_tmp)?;
+        fs::write(work.join("src/lib.rs"), "pub fn remote() {}")?;
+
+        let git = |args: &[&str]| -> Result<(), Box<dyn std::error::Error>> {
+            let status = std::process::Command::new("git")
+                .args(["-c", "user.name=yek", "-c", "user.email=yek@example.com"])
+                .args(args)
+                .current_dir(temp_dir.path())
+                .output()?
+                .status;
+            assert!(status.success(), "git {:?} failed", args);
+            Ok(())
+        };
+        git(&["init", "work"])?;
+        git(&["-C", "work", "add", "."])?;
+        git(&["-C", "work", "commit", "-m", "Initial commit"])?;
+        git(&["clone", "--bare", "work", "remote.git"])?;
+        let url = format!("file://{}", temp_dir.path().join("remote.git").display());
+
+        let clones = || -> Result<Vec<String>, std::io::Error> {
+            fs::read_dir(&clone_tmp)?
+                .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+                .collect()
+        };
+
+        Command::cargo_bin("yek")?
+            .env("TMPDIR", &clone_tmp)
+            .arg(&url)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(
+                ">>>> src/lib.rs\npub fn remote() {}",
+            ));
+        assert_eq!(clones()?, Vec::<String>::new());
+
+        Command::cargo_bin("yek")?
+            .env("TMPDIR", &clone_tmp)
+            .arg(&url)
+            .arg("--keep-clone")
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("Kept clone of"));
+        let kept = clones()?;
+        assert_eq!(kept.len(), 1);
+        assert!(clone_tmp.join(&kept[0]).join("remote/src/lib.rs").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_jsonl_streaming() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "first")?;
+        fs::write(temp_dir.path().join("b.txt"), "second\nline")?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(temp_dir.path())
+            .arg("--jsonl")
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        let filenames = stdout
+            .lines()
+            .map(|line| {
+                let record: serde_json::Value = serde_json::from_str(line)?;
+                Ok(record["filename"].as_str().unwrap_or_default().to_string())
+            })
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        assert_eq!(filenames, vec!["a.txt", "b.txt"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_if_unchanged() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let output_dir = tempdir()?;
+        fs::create_dir_all(temp_dir.path().join("src/deep"))?;
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}")?;
+        fs::write(temp_dir.path().join("src/deep/mod.rs"), "mod a;")?;
+
+        let run = |args: &[&str]| -> Result<String, Box<dyn std::error::Error>> {
+            let output = Command::cargo_bin("yek")?
+                .current_dir(temp_dir.path())
+                .env("FORCE_TTY", "1")
+                .arg(temp_dir.path())
+                .arg("--output-dir")
+                .arg(output_dir.path())
+                .arg("--skip-if-unchanged")
+                .args(args)
+                .output()?;
+            assert!(output.status.success());
+            Ok(String::from_utf8(output.stdout)?.trim().to_string())
+        };
+
+        let first = run(&[])?;
+        let modified = fs::metadata(&first)?.modified()?;
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        // Same inputs: the existing file is reported and left alone
+        let second = run(&[])?;
+        assert_eq!(second, first);
+        assert_eq!(fs::metadata(&second)?.modified()?, modified);
+
+        // A new file changes the checksum, so there is a fresh output
+        fs::write(temp_dir.path().join("lib.rs"), "pub fn lib() {}")?;
+        let third = run(&[])?;
+        assert_ne!(third, first);
+        assert!(fs::read_to_string(&third)?.contains("pub fn lib() {}"));
+
+        // So does an edit in a nested directory
+        fs::write(temp_dir.path().join("src/deep/mod.rs"), "mod b;")?;
+        let fourth = run(&[])?;
+        assert_ne!(fourth, third);
+        assert!(fs::read_to_string(&fourth)?.contains("mod b;"));
+
+        // And a setting that changes the output
+        let fifth = run(&["--line-numbers"])?;
+        assert_ne!(fifth, fourth);
+        assert_eq!(run(&["--line-numbers"])?, fifth);
+        Ok(())
+    }
+
     #[test]
     fn test_multiple_input_dirs() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir1 = tempdir()?;
@@ -245,6 +942,155 @@ mod e2e_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_incremental_streaming_cache_location() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let tmp = tempdir()?;
+        fs::write(temp_dir.path().join("test.rs"), "Test content")?;
+
+        // Streaming writes no output file, so the cache goes to the default output
+        // directory rather than the current directory
+        Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .env("TMPDIR", tmp.path())
+            .arg("--incremental")
+            .arg(".")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Test content"));
+        assert!(!temp_dir.path().join(".yek-cache.json").exists());
+        assert!(tmp.path().join("yek-output/.yek-cache.json").is_file());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("test.rs"), "Test content")?;
+        fs::write(temp_dir.path().join("notes.txt"), "Some notes")?;
+        let output_dir = tempdir()?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .env("FORCE_TTY", "1")
+            .arg(temp_dir.path())
+            .arg("--dry-run")
+            .arg("--output-dir")
+            .arg(output_dir.path())
+            .output()?;
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(stdout.contains("test.rs"));
+        assert!(stdout.contains("notes.txt"));
+        assert!(stdout.contains("2 files, 22 bytes"));
+        assert!(!stdout.contains("Test content"));
+        assert_eq!(fs::read_dir(output_dir.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_only() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("small.rs"), "fn main() {}")?;
+        fs::write(
+            temp_dir.path().join("large.txt"),
+            "The quick brown fox jumps over the lazy dog. ".repeat(20),
+        )?;
+        let output_dir = tempdir()?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .env("FORCE_TTY", "1")
+            .arg(temp_dir.path())
+            .arg("--count-only")
+            .arg("--json")
+            .arg("--output-dir")
+            .arg(output_dir.path())
+            .output()?;
+        assert!(output.status.success());
+
+        let summary: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+        let files = summary["files"].as_array().unwrap();
+        let names: Vec<&str> = files
+            .iter()
+            .map(|f| f["filename"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["large.txt", "small.rs"]);
+        let sum = |key: &str| files.iter().map(|f| f[key].as_u64().unwrap()).sum::<u64>();
+        assert_eq!(summary["total_tokens"].as_u64(), Some(sum("tokens")));
+        assert_eq!(summary["total_bytes"].as_u64(), Some(sum("bytes")));
+        assert_eq!(sum("bytes"), 12 + 45 * 20);
+        assert_eq!(fs::read_dir(output_dir.path())?.count(), 0);
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(temp_dir.path())
+            .arg("--count-only")
+            .arg("--tokenizer")
+            .arg("whitespace")
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(stdout.contains("       180         900  large.txt"));
+        assert!(stdout.contains("2 files, 183 tokens, 912 bytes"));
+        assert!(!stdout.contains("fn main"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_list_from_stdin() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::create_dir(temp_dir.path().join("src"))?;
+        fs::write(temp_dir.path().join("src/lib.rs"), "lib content")?;
+        fs::write(temp_dir.path().join("a.txt"), "a content")?;
+        fs::write(temp_dir.path().join("b.txt"), "b content")?;
+        fs::write(temp_dir.path().join("unlisted.txt"), "unlisted content")?;
+
+        for flag in ["-", "--from-stdin"] {
+            let output = Command::cargo_bin("yek")?
+                .current_dir(temp_dir.path())
+                .arg(flag)
+                .write_stdin("src/lib.rs\nb.txt\n\na.txt\n")
+                .output()?;
+            assert!(output.status.success());
+            let stdout = String::from_utf8(output.stdout)?;
+
+            assert!(!stdout.contains("unlisted content"));
+            let a = stdout.find(">>>> a.txt\na content").unwrap();
+            let b = stdout.find(">>>> b.txt\nb content").unwrap();
+            let lib = stdout.find(">>>> src/lib.rs\nlib content").unwrap();
+            assert!(a < b && b < lib, "unexpected order:\n{}", stdout);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_list_from_stdin_nul_separated() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::create_dir(temp_dir.path().join("my docs"))?;
+        fs::write(
+            temp_dir.path().join("my docs/read me.txt"),
+            "spaced content",
+        )?;
+        fs::write(temp_dir.path().join("a.txt"), "a content")?;
+        fs::write(temp_dir.path().join("unlisted.txt"), "unlisted content")?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg("--stdin-filenames0")
+            .write_stdin("my docs/read me.txt\0a.txt\0")
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+
+        assert!(!stdout.contains("unlisted content"));
+        assert!(stdout.contains(">>>> a.txt\na content"));
+        assert!(stdout.contains(">>>> my docs/read me.txt\nspaced content"));
+        Ok(())
+    }
+
     #[test]
     fn test_gitignore_respected() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = tempdir()?;
@@ -252,10 +1098,15 @@ mod e2e_tests {
         fs::write(temp_dir.path().join("test.log"), "Log content")?;
         fs::write(temp_dir.path().join("test.txt"), "Test content")?;
 
-        Command::cargo_bin("yek")?
-            .arg(temp_dir.path())
-            .assert()
-            .success();
+        // .gitignore is honored without a config file setting respect_gitignore
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(".")
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(stdout.contains("Test content"));
+        assert!(!stdout.contains("Log content"));
 
         Ok(())
     }
@@ -417,4 +1268,190 @@ mod e2e_tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_config_file_discovered_in_current_dir() -> Result<(), Box<dyn std::error::Error>> {
+        // Each supported format is found without --config-file and parsed the same way
+        for (name, content) in [
+            ("yek.toml", "ignore_patterns = [\"*.tmp.txt\"]\n"),
+            ("yek.yaml", "ignore_patterns:\n  - \"*.tmp.txt\"\n"),
+            ("yek.json", r#"{"ignore_patterns": ["*.tmp.txt"]}"#),
+        ] {
+            let temp_dir = tempdir()?;
+            fs::write(temp_dir.path().join("keep.txt"), "Keep me")?;
+            fs::write(temp_dir.path().join("scratch.tmp.txt"), "Scratch content")?;
+            fs::write(temp_dir.path().join(name), content)?;
+
+            let output = Command::cargo_bin("yek")?
+                .current_dir(temp_dir.path())
+                .arg(".")
+                .output()?;
+
+            assert!(output.status.success());
+            let stdout = String::from_utf8(output.stdout)?;
+            assert!(stdout.contains("Keep me"), "{name}: {stdout}");
+            assert!(
+                !stdout.contains("Scratch content"),
+                "{name} should have been picked up: {stdout}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_config_ignores_config_file() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("keep.txt"), "Keep me")?;
+        fs::write(temp_dir.path().join("scratch.tmp.txt"), "Scratch content")?;
+        fs::write(
+            temp_dir.path().join("yek.toml"),
+            "ignore_patterns = [\"*.tmp.txt\"]\n",
+        )?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg("--no-config")
+            .arg(".")
+            .output()?;
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(stdout.contains("Keep me"));
+        assert!(
+            stdout.contains("Scratch content"),
+            "yek.toml should not be applied with --no-config: {stdout}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_quiet_writes_nothing_to_stderr() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let output_dir = temp_dir.path().join("output");
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}")?;
+
+        let output = Command::cargo_bin("yek")?
+            .env("FORCE_TTY", "1")
+            .arg("--quiet")
+            .arg("--output-dir")
+            .arg(&output_dir)
+            .arg(temp_dir.path())
+            .output()?;
+
+        assert!(output.status.success());
+        assert!(
+            output.stderr.is_empty(),
+            "expected no progress output, got: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_summary_line_on_stderr() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let output_dir = temp_dir.path().join("output");
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}")?;
+        fs::write(temp_dir.path().join("lib.rs"), "pub fn lib() {}")?;
+
+        let output = Command::cargo_bin("yek")?
+            .env("FORCE_TTY", "1")
+            .arg("--output-dir")
+            .arg(&output_dir)
+            .arg(temp_dir.path())
+            .output()?;
+
+        assert!(output.status.success());
+        let path = String::from_utf8(output.stdout)?.trim().to_string();
+        let size = fs::metadata(&path)?.len();
+        let stderr = String::from_utf8(output.stderr)?;
+        let summary = stderr.lines().last().unwrap_or_default();
+        // Byte mode doesn't tokenize the output just for the summary
+        assert_eq!(
+            summary,
+            format!("yek: 2 files, {} B → {}", size, path),
+            "stderr: {stderr}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_summary_line_counts_tokens_in_token_mode() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let output_dir = temp_dir.path().join("output");
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}")?;
+
+        let output = Command::cargo_bin("yek")?
+            .env("FORCE_TTY", "1")
+            .arg("--tokens")
+            .arg("10k")
+            .arg("--output-dir")
+            .arg(&output_dir)
+            .arg(temp_dir.path())
+            .output()?;
+
+        assert!(output.status.success());
+        let path = String::from_utf8(output.stdout)?.trim().to_string();
+        let size = fs::metadata(&path)?.len();
+        let stderr = String::from_utf8(output.stderr)?;
+        let summary = stderr.lines().last().unwrap_or_default();
+        assert!(
+            summary.starts_with(&format!("yek: 1 files, {} B, ", size)),
+            "stderr: {stderr}"
+        );
+        assert!(
+            summary.ends_with(&format!(" tokens → {}", path)),
+            "stderr: {stderr}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delimiter_collision_warning() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(
+            temp_dir.path().join("merge.txt"),
+            "<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> branch\n",
+        )?;
+
+        // The default ">>>>" delimiter collides with the conflict marker
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(temp_dir.path())
+            .output()?;
+        assert!(output.status.success());
+        let stderr = String::from_utf8(output.stderr)?;
+        assert!(
+            stderr.contains("Warning: 1 file(s) contain lines starting with the '>>>>' delimiter"),
+            "stderr: {stderr}"
+        );
+
+        // Nothing is printed with --quiet
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg("--quiet")
+            .arg(temp_dir.path())
+            .output()?;
+        assert!(output.status.success());
+        assert!(String::from_utf8(output.stderr)?.is_empty());
+
+        // A custom delimiter is used for headers and doesn't collide
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg("--output-template")
+            .arg("===FILE=== FILE_PATH\nFILE_CONTENT")
+            .arg(temp_dir.path())
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(stdout.starts_with("===FILE=== merge.txt\n<<<<<<< HEAD"));
+        assert!(String::from_utf8(output.stderr)?.is_empty());
+
+        Ok(())
+    }
 }
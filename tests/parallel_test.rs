@@ -1,11 +1,16 @@
+use indicatif::{ProgressBar, ProgressDrawTarget};
 use normalize_path::NormalizePath;
 use std::collections::HashMap;
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 use tempfile::tempdir;
+use yek::cache::{FileCache, CACHE_FILE_NAME};
 use yek::config::YekConfig;
-use yek::parallel::process_files_parallel;
+use yek::parallel::{
+    normalize_path, process_file_list, process_files_parallel, process_files_parallel_with,
+    BinaryFiles, BinaryReason, ReadErrors,
+};
 
 #[test]
 fn test_normalize_path_unix_style() {
@@ -23,6 +28,42 @@ fn test_normalize_path_unix_style() {
     );
 }
 
+#[test]
+fn test_normalize_path_collapses_dot_components() {
+    let base = Path::new("/repo");
+    assert_eq!(
+        normalize_path(Path::new("/repo/foo/./bar"), base),
+        "foo/bar"
+    );
+    assert_eq!(normalize_path(Path::new("/repo/foo/../bar"), base), "bar");
+    assert_eq!(
+        normalize_path(Path::new("/repo/./foo/bar.txt"), base),
+        "foo/bar.txt"
+    );
+    assert_eq!(normalize_path(Path::new("/repo/a/b/../../c"), base), "c");
+    assert_eq!(
+        normalize_path(Path::new("/repo/../repo/x.rs"), base),
+        "x.rs"
+    );
+    // The base itself is normalized too
+    assert_eq!(
+        normalize_path(Path::new("/repo/src/lib.rs"), Path::new("/repo/src/..")),
+        "src/lib.rs"
+    );
+
+    // Relative paths, with an empty base
+    let base = Path::new("");
+    assert_eq!(normalize_path(Path::new("./foo"), base), "foo");
+    assert_eq!(normalize_path(Path::new("foo/./bar"), base), "foo/bar");
+    assert_eq!(normalize_path(Path::new("foo/../bar"), base), "bar");
+    assert_eq!(
+        normalize_path(Path::new("../outside/x"), base),
+        "../outside/x"
+    );
+    // `..` can't climb above the root
+    assert_eq!(normalize_path(Path::new("/../etc"), base), "/etc");
+}
+
 #[test]
 fn test_normalize_path_windows_style() {
     let input = Path::new("C:\\Program Files\\Yek");
@@ -135,3 +176,662 @@ fn test_process_files_parallel_walk_error() {
     let processed_files = result.unwrap();
     assert_eq!(processed_files.len(), 0); // No files processed due to walk error
 }
+
+#[test]
+fn test_process_files_parallel_respects_gitignore() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::write(base.join(".gitignore"), "target/\n").unwrap();
+    fs::create_dir_all(base.join("target/debug")).unwrap();
+    fs::write(base.join("target/debug/out.txt"), "build output").unwrap();
+    fs::create_dir(base.join("src")).unwrap();
+    fs::write(base.join("src/main.rs"), "fn main() {}").unwrap();
+    // Nested .gitignore files are honored too
+    fs::write(base.join("src/.gitignore"), "*.gen.rs\n").unwrap();
+    fs::write(base.join("src/schema.gen.rs"), "// generated").unwrap();
+
+    let config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    let names: Vec<&str> = result.iter().map(|pf| pf.rel_path.as_str()).collect();
+
+    assert!(names.contains(&"src/main.rs"));
+    assert!(!names.iter().any(|n| n.starts_with("target/")));
+    assert!(!names.contains(&"src/schema.gen.rs"));
+}
+
+#[test]
+fn test_process_files_parallel_gitignore_disabled() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::write(base.join(".gitignore"), "target/\n").unwrap();
+    fs::create_dir(base.join("target")).unwrap();
+    fs::write(base.join("target/out.txt"), "build output").unwrap();
+    fs::write(base.join("debug.log"), "log").unwrap();
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    config.respect_gitignore = Some(false);
+    // Custom ignore patterns still apply on top
+    config.ignore_patterns = vec!["*.log".to_string()];
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    let names: Vec<&str> = result.iter().map(|pf| pf.rel_path.as_str()).collect();
+
+    assert!(names.contains(&"target/out.txt"));
+    assert!(!names.contains(&"debug.log"));
+}
+
+#[test]
+fn test_process_files_parallel_null_byte_tolerance() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::write(
+        base.join("stray.txt"),
+        b"abc\0def ghi jkl mno pqr stu vwx yz",
+    )
+    .unwrap();
+    fs::write(base.join("blob.dat"), [0u8, 1, 0, 2, 0, 3]).unwrap();
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    let names: Vec<&str> = result.iter().map(|pf| pf.rel_path.as_str()).collect();
+    assert_eq!(names, vec!["stray.txt"]);
+
+    config.null_byte_tolerance = Some(0.0);
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    assert!(result.is_empty());
+}
+
+#[test]
+fn test_process_files_parallel_max_file_size() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::write(base.join("big.json"), "x".repeat(2_000_000)).unwrap();
+    fs::write(base.join("small.txt"), "small").unwrap();
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    config.max_file_size = Some("1MB".to_string());
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    let names: Vec<&str> = result.iter().map(|pf| pf.rel_path.as_str()).collect();
+    assert_eq!(names, vec!["small.txt"]);
+}
+
+#[test]
+fn test_process_files_parallel_ignore_pattern_globs() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::write(base.join("debug.log"), "log").unwrap();
+    fs::create_dir_all(base.join("web/node_modules/pkg")).unwrap();
+    fs::write(base.join("web/node_modules/pkg/index.js"), "module").unwrap();
+    fs::write(base.join("web/app.js"), "app").unwrap();
+    fs::create_dir_all(base.join("docs/generated")).unwrap();
+    fs::write(base.join("docs/generated/api.md"), "api").unwrap();
+    fs::write(base.join("docs/notes.txt"), "notes").unwrap();
+    fs::write(base.join("docs/guide.md"), "guide").unwrap();
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    config.ignore_patterns = vec![
+        "*.log".to_string(),
+        "**/node_modules/**".to_string(),
+        // Literal paths keep working, both for files and directories
+        "docs/notes.txt".to_string(),
+        "generated".to_string(),
+    ];
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    let mut names: Vec<&str> = result.iter().map(|pf| pf.rel_path.as_str()).collect();
+    names.sort();
+
+    assert_eq!(names, vec!["docs/guide.md", "web/app.js"]);
+}
+
+#[test]
+fn test_process_files_parallel_include_patterns() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::create_dir_all(base.join("src/bin")).unwrap();
+    fs::write(base.join("src/lib.rs"), "lib").unwrap();
+    fs::write(base.join("src/bin/tool.rs"), "tool").unwrap();
+    fs::write(base.join("src/generated.rs"), "generated").unwrap();
+    fs::write(base.join("README.md"), "readme").unwrap();
+    fs::write(base.join("build.py"), "py").unwrap();
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    config.include_patterns = vec!["*.rs".to_string()];
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    let mut names: Vec<&str> = result.iter().map(|pf| pf.rel_path.as_str()).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec!["src/bin/tool.rs", "src/generated.rs", "src/lib.rs"]
+    );
+
+    // Ignore patterns still subtract from the included set
+    config.ignore_patterns = vec!["src/generated.rs".to_string()];
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    let mut names: Vec<&str> = result.iter().map(|pf| pf.rel_path.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["src/bin/tool.rs", "src/lib.rs"]);
+}
+
+#[test]
+fn test_process_files_parallel_many_files_deterministic() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    for dir in ["src", "docs", "tests"] {
+        fs::create_dir(base.join(dir)).unwrap();
+        for i in 0..100 {
+            fs::write(
+                base.join(dir).join(format!("file_{i:03}.txt")),
+                format!("{dir} {i}"),
+            )
+            .unwrap();
+        }
+    }
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    config.priority_rules = vec![yek::priority::PriorityRule {
+        pattern: "^src/".to_string(),
+        score: 50,
+        ..Default::default()
+    }];
+    let boosts: HashMap<String, i32> = HashMap::new();
+
+    let first = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    assert_eq!(first.len(), 300);
+    for _ in 0..3 {
+        let again = process_files_parallel(base, &config, &boosts).expect("processing failed");
+        let summary = |files: &[yek::parallel::ProcessedFile]| {
+            files
+                .iter()
+                .map(|f| {
+                    (
+                        f.rel_path.clone(),
+                        f.priority,
+                        f.file_index,
+                        f.content.clone(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(summary(&first), summary(&again));
+    }
+
+    // Content and priority stay attached to the right path
+    for f in &first {
+        let (dir, name) = f.rel_path.split_once('/').unwrap();
+        let i: usize = name["file_".len()..name.len() - 4].parse().unwrap();
+        assert_eq!(f.content, format!("{dir} {i}"));
+        assert_eq!(f.priority, if dir == "src" { 50 } else { 0 });
+    }
+}
+
+#[test]
+fn test_process_file_list_priority_order() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::create_dir(base.join("src")).unwrap();
+    fs::write(base.join("src/main.rs"), "main").unwrap();
+    fs::write(base.join("notes.txt"), "notes").unwrap();
+    fs::write(base.join("other.txt"), "other").unwrap();
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    config.priority_rules = vec![yek::priority::PriorityRule {
+        pattern: "^src/".to_string(),
+        score: 10,
+        ..Default::default()
+    }];
+    let paths = vec![
+        std::path::PathBuf::from("notes.txt"),
+        // Absolute paths are made relative to the base dir
+        base.join("src/main.rs"),
+        std::path::PathBuf::from("./notes.txt"),
+        std::path::PathBuf::from("missing.txt"),
+    ];
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let result =
+        process_file_list(base, &paths, &config, &boosts, None, None).expect("processing failed");
+    let names: Vec<(&str, i32)> = result
+        .iter()
+        .map(|pf| (pf.rel_path.as_str(), pf.priority))
+        .collect();
+    assert_eq!(names, vec![("src/main.rs", 10), ("notes.txt", 0)]);
+}
+
+#[test]
+fn test_process_files_parallel_incremental_cache() {
+    let repo = tempdir().expect("failed to create temp dir");
+    let out = tempdir().expect("failed to create temp dir");
+    let base = repo.path();
+    fs::write(base.join("a.txt"), "alpha").unwrap();
+    fs::write(base.join("b.txt"), "beta").unwrap();
+    fs::write(base.join("c.txt"), "gamma").unwrap();
+
+    let config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        out.path().to_string_lossy().to_string(),
+    );
+    let boosts: HashMap<String, i32> = HashMap::new();
+
+    // First run reads everything and writes the cache
+    let cache = FileCache::load(out.path(), &config);
+    let first = process_files_parallel_with(base, &config, &boosts, Some(&cache), None, None, None)
+        .unwrap();
+    assert_eq!(first.len(), 3);
+    assert_eq!(cache.reads(), 3);
+    cache.save().unwrap();
+    assert!(out.path().join(CACHE_FILE_NAME).exists());
+
+    // Second run only re-reads the modified file
+    fs::write(base.join("b.txt"), "beta, changed").unwrap();
+    let cache = FileCache::load(out.path(), &config);
+    let second =
+        process_files_parallel_with(base, &config, &boosts, Some(&cache), None, None, None)
+            .unwrap();
+    assert_eq!(cache.reads(), 1);
+    let mut contents: Vec<(&str, &str)> = second
+        .iter()
+        .map(|f| (f.rel_path.as_str(), f.content.as_str()))
+        .collect();
+    contents.sort();
+    assert_eq!(
+        contents,
+        vec![
+            ("a.txt", "alpha"),
+            ("b.txt", "beta, changed"),
+            ("c.txt", "gamma")
+        ]
+    );
+
+    // An otherwise valid cache written by another version is ignored
+    cache.save().unwrap();
+    let cache_path = out.path().join(CACHE_FILE_NAME);
+    let saved = fs::read_to_string(&cache_path).unwrap();
+    assert!(saved.contains(r#""version":3"#));
+    fs::write(
+        &cache_path,
+        saved.replace(r#""version":3"#, r#""version":0"#),
+    )
+    .unwrap();
+    let cache = FileCache::load(out.path(), &config);
+    process_files_parallel_with(base, &config, &boosts, Some(&cache), None, None, None).unwrap();
+    assert_eq!(cache.reads(), 3);
+    cache.save().unwrap();
+
+    // So is a cache whose content was read with another encoding
+    let latin1 = YekConfig {
+        encoding: Some("latin1".to_string()),
+        ..config.clone()
+    };
+    let cache = FileCache::load(out.path(), &latin1);
+    process_files_parallel_with(base, &latin1, &boosts, Some(&cache), None, None, None).unwrap();
+    assert_eq!(cache.reads(), 3);
+}
+
+#[test]
+fn test_file_cache_does_not_keep_read_errors() {
+    let temp_dir = tempdir().unwrap();
+    let out = tempdir().unwrap();
+    let file = temp_dir.path().join("a.txt");
+    fs::write(&file, "alpha").unwrap();
+    let config = YekConfig::default();
+
+    // A failed read is not cached...
+    let cache = FileCache::load(out.path(), &config);
+    let content = cache.get_or_read(&file, || Err(std::io::Error::other("denied")));
+    assert_eq!(content, None);
+    cache.save().unwrap();
+
+    // ...so the unchanged file is read again on the next run
+    let cache = FileCache::load(out.path(), &config);
+    let content = cache.get_or_read(&file, || Ok(Some("alpha".to_string())));
+    assert_eq!(content.as_deref(), Some("alpha"));
+    assert_eq!(cache.reads(), 1);
+}
+
+#[test]
+fn test_process_files_parallel_progress() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::write(base.join("a.txt"), "alpha").unwrap();
+    fs::write(base.join("b.txt"), "beta").unwrap();
+    fs::write(base.join("image.png"), [0x89, b'P', b'N', b'G', 0]).unwrap();
+
+    let config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let progress = ProgressBar::with_draw_target(Some(0), ProgressDrawTarget::hidden());
+    let result =
+        process_files_parallel_with(base, &config, &boosts, None, Some(&progress), None, None)
+            .unwrap();
+
+    assert_eq!(result.len(), 2);
+    // Every candidate found by the walk is counted, including skipped binaries
+    assert_eq!(progress.length(), Some(3));
+    assert_eq!(progress.position(), 3);
+    assert_eq!(progress.message(), "9 B read");
+}
+
+#[test]
+fn test_process_files_parallel_binary_reasons() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::create_dir_all(base.join("assets")).unwrap();
+    fs::write(
+        base.join("assets/logo.png"),
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', 0, 0],
+    )
+    .unwrap();
+    fs::write(base.join("data.txt"), "ab\0\0\0\0cd").unwrap();
+    fs::write(base.join("readme.txt"), "plain text").unwrap();
+
+    let config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let binaries = BinaryFiles::default();
+    let result =
+        process_files_parallel_with(base, &config, &boosts, None, None, Some(&binaries), None)
+            .unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].rel_path, "readme.txt");
+    assert_eq!(
+        binaries.into_sorted(),
+        vec![
+            ("assets/logo.png".to_string(), BinaryReason::Extension),
+            ("data.txt".to_string(), BinaryReason::Content),
+        ]
+    );
+}
+
+#[test]
+fn test_process_files_parallel_force_text_extensions() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::write(base.join("app.min.js"), "var a=\"\0\0\0\0\";").unwrap();
+    fs::write(base.join("data.js"), "ab\0\0\0\0cd").unwrap();
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    assert!(result.is_empty());
+
+    // Only the forced extension skips binary detection
+    config.force_text_extensions = vec![".MIN.JS".to_string()];
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].rel_path, "app.min.js");
+    assert_eq!(result[0].content, "var a=\"\0\0\0\0\";");
+}
+
+#[test]
+fn test_process_files_parallel_ignore_file() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::write(base.join(".dockerignore"), "*.tmp\n!keep.tmp\n").unwrap();
+    fs::write(base.join("a.tmp"), "scratch").unwrap();
+    fs::write(base.join("keep.tmp"), "kept").unwrap();
+    fs::write(base.join("main.rs"), "fn main() {}").unwrap();
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    config.ignore_files = vec![".dockerignore".to_string()];
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    let mut paths: Vec<_> = result.iter().map(|f| f.rel_path.as_str()).collect();
+    paths.sort();
+    assert_eq!(paths, vec!["keep.tmp", "main.rs"]);
+}
+
+#[test]
+fn test_process_files_parallel_scan_limits() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    for i in 0..10 {
+        fs::write(base.join(format!("file{i}.txt")), "12345").unwrap();
+    }
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    let boosts: HashMap<String, i32> = HashMap::new();
+
+    config.max_scan_files = 3;
+    let err = process_files_parallel(base, &config, &boosts).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "max_scan_files: more than 3 files found in {}. Raise max_scan_files, \
+             or set it to 0, if this is intended",
+            base.display()
+        )
+    );
+
+    config.max_scan_files = 0;
+    config.max_scan_bytes = "20B".to_string();
+    let err = process_files_parallel(base, &config, &boosts).unwrap_err();
+    assert!(err.to_string().starts_with("max_scan_bytes: files in "));
+
+    // Both limits can be turned off for intentionally large runs
+    config.max_scan_bytes = "0".to_string();
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    assert_eq!(result.len(), 10);
+}
+
+#[test]
+fn test_process_files_parallel_yekignore() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::create_dir_all(base.join("docs/drafts")).unwrap();
+    fs::write(base.join(".yekignore"), "*.scratch\n").unwrap();
+    fs::write(base.join("keep.txt"), "keep").unwrap();
+    fs::write(base.join("notes.scratch"), "scratch").unwrap();
+    fs::write(base.join("docs/guide.md"), "guide").unwrap();
+    fs::write(base.join("docs/old.scratch"), "old").unwrap();
+    // A nested .yekignore only applies below its own directory
+    fs::write(base.join("docs/.yekignore"), "drafts/\n").unwrap();
+    fs::write(base.join("docs/drafts/wip.md"), "wip").unwrap();
+
+    let config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let result = process_files_parallel(base, &config, &boosts).expect("processing failed");
+    let mut names: Vec<&str> = result.iter().map(|f| f.rel_path.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["docs/guide.md", "keep.txt"]);
+}
+
+#[test]
+fn test_process_files_parallel_encoding() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    // "Grüße aus Köln" in Latin-1
+    fs::write(base.join("latin1.txt"), b"Gr\xfc\xdfe aus K\xf6ln").unwrap();
+    fs::write(base.join("utf8.txt"), "Grüße").unwrap();
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![base.to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    let boosts: HashMap<String, i32> = HashMap::new();
+    let content_of = |files: &[yek::parallel::ProcessedFile], name: &str| {
+        files
+            .iter()
+            .find(|f| f.rel_path == name)
+            .map(|f| f.content.clone())
+    };
+
+    config.encoding = Some("latin1".to_string());
+    let result = process_files_parallel(base, &config, &boosts).unwrap();
+    assert_eq!(
+        content_of(&result, "latin1.txt").as_deref(),
+        Some("Grüße aus Köln")
+    );
+
+    // Strict UTF-8 skips the Latin-1 file instead of corrupting it, and reports it
+    config.encoding = Some("utf-8".to_string());
+    let read_errors = ReadErrors::default();
+    let result =
+        process_files_parallel_with(base, &config, &boosts, None, None, None, Some(&read_errors))
+            .unwrap();
+    assert_eq!(content_of(&result, "latin1.txt"), None);
+    assert_eq!(content_of(&result, "utf8.txt").as_deref(), Some("Grüße"));
+    assert_eq!(
+        read_errors.into_sorted(),
+        vec![("latin1.txt".to_string(), "not valid utf-8 text".to_string())]
+    );
+
+    config.encoding = Some("auto".to_string());
+    let result = process_files_parallel(base, &config, &boosts).unwrap();
+    assert_eq!(
+        content_of(&result, "latin1.txt").as_deref(),
+        Some("Grüße aus Köln")
+    );
+    assert_eq!(content_of(&result, "utf8.txt").as_deref(), Some("Grüße"));
+}
+
+#[test]
+fn test_process_files_parallel_hidden() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::create_dir_all(base.join(".config")).unwrap();
+    fs::create_dir_all(base.join(".git")).unwrap();
+    fs::write(base.join(".env"), "SECRET=1").unwrap();
+    fs::write(base.join(".config/app.toml"), "name = \"app\"").unwrap();
+    fs::write(base.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+    fs::write(base.join("main.rs"), "fn main() {}").unwrap();
+
+    let paths = |config: &YekConfig| {
+        let mut paths: Vec<String> = process_files_parallel(base, config, &HashMap::new())
+            .expect("process_files_parallel failed")
+            .into_iter()
+            .map(|f| f.rel_path)
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    let mut config = YekConfig::default();
+    assert_eq!(paths(&config), vec!["main.rs"]);
+
+    config.hidden = true;
+    assert_eq!(paths(&config), vec![".config/app.toml", ".env", "main.rs"]);
+
+    // Ignore and include patterns apply to hidden files as usual
+    config.ignore_patterns = vec![".env".to_string()];
+    assert_eq!(paths(&config), vec![".config/app.toml", "main.rs"]);
+    config.include_patterns = vec!["*.toml".to_string()];
+    assert_eq!(paths(&config), vec![".config/app.toml"]);
+}
+
+#[test]
+fn test_process_files_parallel_max_depth() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::create_dir_all(base.join("a/b/c")).unwrap();
+    fs::write(base.join("root.txt"), "root").unwrap();
+    fs::write(base.join("a/one.txt"), "one").unwrap();
+    fs::write(base.join("a/b/c/three.txt"), "three").unwrap();
+
+    let paths = |max_depth: Option<usize>| {
+        let config = YekConfig {
+            max_depth,
+            ..Default::default()
+        };
+        let mut paths: Vec<String> = process_files_parallel(base, &config, &HashMap::new())
+            .expect("process_files_parallel failed")
+            .into_iter()
+            .map(|f| f.rel_path)
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    assert_eq!(paths(Some(0)), vec!["root.txt"]);
+    assert_eq!(paths(Some(1)), vec!["a/one.txt", "root.txt"]);
+    assert_eq!(
+        paths(None),
+        vec!["a/b/c/three.txt", "a/one.txt", "root.txt"]
+    );
+}
+
+#[test]
+fn test_process_files_parallel_export_ignore() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let base = temp_dir.path();
+    fs::create_dir_all(base.join("docs")).unwrap();
+    fs::create_dir_all(base.join("src")).unwrap();
+    fs::write(base.join("docs/guide.md"), "# Guide").unwrap();
+    fs::write(base.join("src/main.rs"), "fn main() {}").unwrap();
+    fs::write(base.join("src/fixture.rs"), "// fixture").unwrap();
+    fs::write(
+        base.join(".gitattributes"),
+        "# Not in release archives\ndocs/ export-ignore\n*.rs text eol=lf\nsrc/fixture.rs -diff export-ignore\n",
+    )
+    .unwrap();
+
+    let paths = |config: &YekConfig| {
+        let mut paths: Vec<String> = process_files_parallel(base, config, &HashMap::new())
+            .expect("process_files_parallel failed")
+            .into_iter()
+            .map(|f| f.rel_path)
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    // Outside a Git repository, .gitattributes has no effect
+    let mut config = YekConfig::default();
+    assert_eq!(
+        paths(&config),
+        vec!["docs/guide.md", "src/fixture.rs", "src/main.rs"]
+    );
+
+    fs::create_dir(base.join(".git")).unwrap();
+    assert_eq!(paths(&config), vec!["src/main.rs"]);
+
+    config.respect_gitignore = Some(false);
+    assert_eq!(
+        paths(&config),
+        vec!["docs/guide.md", "src/fixture.rs", "src/main.rs"]
+    );
+}
@@ -2,10 +2,17 @@
 mod priority_tests {
     use std::collections::HashMap;
     use std::fs;
+    use std::path::Path;
     use tempfile::tempdir;
+    use yek::config::YekConfig;
     use yek::priority::{
-        compute_recentness_boost, get_file_priority, get_recent_commit_times_git2, PriorityRule,
+        compute_git_boost, compute_recentness_boost, depth_priority, explain_file_priority,
+        get_changed_files_git2, get_file_priority, get_file_priority_with,
+        get_file_priority_with_extensions, get_git_history_git2, get_recent_commit_counts_git2,
+        get_recent_commit_hashes_git2, get_recent_commit_times_git2, get_tracked_files_git2,
+        with_default_syntax, PriorityExplanation, PriorityRule,
     };
+    use yek::serialize_repo;
 
     #[test]
     fn test_get_file_priority_multiple_matches() {
@@ -13,15 +20,282 @@ mod priority_tests {
             PriorityRule {
                 pattern: r"src/.*".to_string(),
                 score: 5,
+                ..Default::default()
             },
             PriorityRule {
                 pattern: r".*\.rs".to_string(),
                 score: 10,
+                ..Default::default()
             },
         ];
         assert_eq!(get_file_priority("src/main.rs", &rules), 15);
     }
 
+    #[test]
+    fn test_get_file_priority_combine() {
+        let rules = vec![
+            PriorityRule {
+                pattern: r"^src/".to_string(),
+                score: 10,
+                ..Default::default()
+            },
+            PriorityRule {
+                pattern: r"\.rs$".to_string(),
+                score: 20,
+                ..Default::default()
+            },
+        ];
+        assert_eq!(get_file_priority_with("src/main.rs", &rules, "max"), 20);
+        assert_eq!(get_file_priority_with("src/main.rs", &rules, "sum"), 30);
+        assert_eq!(get_file_priority_with("src/notes.md", &rules, "max"), 10);
+        assert_eq!(get_file_priority_with("README.md", &rules, "max"), 0);
+    }
+
+    #[test]
+    fn test_extension_priorities() {
+        let extensions = HashMap::from([("rs".to_string(), 100), (".MD".to_string(), 10)]);
+        let rules = vec![PriorityRule {
+            pattern: r"^docs/".to_string(),
+            score: 500,
+            ..Default::default()
+        }];
+        assert_eq!(
+            get_file_priority_with_extensions("src/main.rs", &[], &extensions, "sum"),
+            100
+        );
+        assert_eq!(
+            get_file_priority_with_extensions("README.md", &rules, &extensions, "sum"),
+            10
+        );
+        assert_eq!(
+            get_file_priority_with_extensions("Cargo.toml", &rules, &extensions, "sum"),
+            0
+        );
+        // A matching rule takes precedence over the extension
+        assert_eq!(
+            get_file_priority_with_extensions("docs/guide.md", &rules, &extensions, "sum"),
+            500
+        );
+
+        // Without any rules, source files rank above docs in the output
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("a_notes.md"), "# Notes").unwrap();
+        fs::write(temp_dir.path().join("b_main.rs"), "fn main() {}").unwrap();
+        fs::write(temp_dir.path().join("c_config.toml"), "x = 1").unwrap();
+        let config = YekConfig {
+            input_dirs: vec![temp_dir.path().to_string_lossy().to_string()],
+            extension_priorities: extensions,
+            ..Default::default()
+        };
+        let (_, files) = serialize_repo(&config).unwrap();
+        let ranked: Vec<(&str, i32)> = files
+            .iter()
+            .map(|f| (f.rel_path.as_str(), f.priority))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![("c_config.toml", 0), ("a_notes.md", 10), ("b_main.rs", 100)]
+        );
+    }
+
+    #[test]
+    fn test_explain_priority_names_winning_rule() {
+        let rules = vec![
+            PriorityRule {
+                pattern: r"^src/".to_string(),
+                score: 5,
+                ..Default::default()
+            },
+            PriorityRule {
+                pattern: r"\.rs$".to_string(),
+                score: 20,
+                ..Default::default()
+            },
+        ];
+        let none = HashMap::new();
+        assert_eq!(
+            explain_file_priority("src/lib.rs", &rules, &none, "sum"),
+            PriorityExplanation {
+                rule: Some(r"\.rs$".to_string()),
+                rule_score: 20,
+                priority: 25,
+            }
+        );
+        assert_eq!(
+            explain_file_priority("src/lib.rs", &rules, &none, "max").priority,
+            20
+        );
+        let extensions = HashMap::from([("md".to_string(), 3)]);
+        assert_eq!(
+            explain_file_priority("README.md", &rules, &extensions, "sum"),
+            PriorityExplanation {
+                rule: Some("extension .md".to_string()),
+                rule_score: 3,
+                priority: 3,
+            }
+        );
+
+        // The explain summary lists the winner next to each included file
+        let temp_dir = tempdir().unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs"), "pub fn lib() {}").unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "notes").unwrap();
+        let config = YekConfig {
+            input_dirs: vec![temp_dir.path().to_string_lossy().to_string()],
+            priority_rules: rules,
+            explain: true,
+            ..Default::default()
+        };
+        let (output, _) = serialize_repo(&config).unwrap();
+        assert_eq!(
+            output,
+            [
+                "PRIORITY   SCORE   BOOST  PATH  RULE",
+                "       0       0       0  notes.txt  -",
+                r"      25      20       0  src/lib.rs  \.rs$",
+            ]
+            .join("\n")
+        );
+
+        // With several input directories, rules are explained against the path within
+        // its directory, not the one with the directory's name in front
+        let other_dir = tempdir().unwrap();
+        fs::create_dir(other_dir.path().join("src")).unwrap();
+        fs::write(other_dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        let config = YekConfig {
+            input_dirs: vec![
+                temp_dir.path().to_string_lossy().to_string(),
+                other_dir.path().to_string_lossy().to_string(),
+            ],
+            ..config
+        };
+        let (output, _) = serialize_repo(&config).unwrap();
+        let label = |dir: &tempfile::TempDir| {
+            let path = dir.path().canonicalize().unwrap();
+            path.file_name().unwrap().to_string_lossy().to_string()
+        };
+        let mut rows = vec![
+            format!(
+                "      25      20       0  {}/src/lib.rs  \\.rs$",
+                label(&temp_dir)
+            ),
+            format!(
+                "      25      20       0  {}/src/main.rs  \\.rs$",
+                label(&other_dir)
+            ),
+        ];
+        rows.sort();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].ends_with("/notes.txt  -"), "{output}");
+        let mut explained = lines[2..].to_vec();
+        explained.sort();
+        assert_eq!(explained, rows);
+    }
+
+    #[test]
+    fn test_glob_rule_matches_like_equivalent_regex() {
+        let glob = PriorityRule {
+            pattern: "src/**/*.rs".to_string(),
+            score: 10,
+            syntax: Some("glob".to_string()),
+            ..Default::default()
+        };
+        let regex = PriorityRule {
+            pattern: r"^src/(.*/)?[^/]*\.rs$".to_string(),
+            score: 10,
+            ..Default::default()
+        };
+        let paths = [
+            "src/main.rs",
+            "src/parser/lexer.rs",
+            "src/a/b/c.rs",
+            "src/main.rs.bak",
+            "srcx/main.rs",
+            "tests/src/main.rs",
+            "README.md",
+        ];
+        for path in paths {
+            assert_eq!(glob.matches(path), regex.matches(path), "{}", path);
+        }
+        assert!(glob.matches("src/parser/lexer.rs"));
+        assert!(!glob.matches("tests/src/main.rs"));
+
+        // A single `*` doesn't cross directories
+        let shallow = PriorityRule {
+            pattern: "src/*.rs".to_string(),
+            syntax: Some("glob".to_string()),
+            ..Default::default()
+        };
+        assert!(shallow.matches("src/main.rs"));
+        assert!(!shallow.matches("src/parser/lexer.rs"));
+    }
+
+    #[test]
+    fn test_priority_rule_default_syntax() {
+        let rules = vec![
+            PriorityRule {
+                pattern: "docs/*.md".to_string(),
+                score: 5,
+                ..Default::default()
+            },
+            PriorityRule {
+                pattern: r"^src/.*".to_string(),
+                score: 20,
+                syntax: Some("regex".to_string()),
+                ..Default::default()
+            },
+        ];
+        // Without a syntax a rule is a regex, where "docs/*.md" means something else
+        assert_eq!(get_file_priority("docs/intro.md", &rules), 0);
+
+        let rules = with_default_syntax(&rules, "glob");
+        assert_eq!(get_file_priority("docs/intro.md", &rules), 5);
+        // Rules with their own syntax keep it
+        assert_eq!(get_file_priority("src/lib.rs", &rules), 20);
+    }
+
+    #[test]
+    fn test_get_file_priority_extensions_and_exclude_pattern() {
+        let rules = vec![PriorityRule {
+            pattern: r"src/.*\.rs".to_string(),
+            score: 50,
+            extensions: vec!["rs".to_string()],
+            exclude_pattern: Some(r".*_test\.rs".to_string()),
+            ..Default::default()
+        }];
+        assert_eq!(get_file_priority("src/main.rs", &rules), 50);
+        assert_eq!(get_file_priority("src/parser_test.rs", &rules), 0);
+        assert_eq!(get_file_priority("src/main.rs.bak", &rules), 0);
+        assert_eq!(get_file_priority("tests/main.rs", &rules), 0);
+
+        // Extensions alone filter by file type, with or without the leading dot
+        let rules = vec![PriorityRule {
+            pattern: "^src/".to_string(),
+            score: 10,
+            extensions: vec![".rs".to_string(), "toml".to_string()],
+            ..Default::default()
+        }];
+        assert_eq!(get_file_priority("src/lib.rs", &rules), 10);
+        assert_eq!(get_file_priority("src/Cargo.toml", &rules), 10);
+        assert_eq!(get_file_priority("src/README.md", &rules), 0);
+        assert_eq!(get_file_priority("src/Makefile", &rules), 0);
+    }
+
+    #[test]
+    fn test_priority_rule_new_fields_optional_in_config() {
+        let rule: PriorityRule = serde_yaml::from_str("pattern: \"^src/\"\nscore: 10\n").unwrap();
+        assert!(rule.extensions.is_empty());
+        assert_eq!(rule.exclude_pattern, None);
+
+        let rule: PriorityRule = serde_yaml::from_str(
+            "pattern: \"^src/\"\nscore: 10\nextensions: [rs]\nexclude_pattern: \"_test\\\\.rs$\"\n",
+        )
+        .unwrap();
+        assert_eq!(rule.extensions, vec!["rs".to_string()]);
+        assert_eq!(rule.exclude_pattern.as_deref(), Some(r"_test\.rs$"));
+    }
+
     #[test]
     fn test_compute_recentness_boost_empty() {
         let commit_times = HashMap::new();
@@ -66,10 +340,12 @@ mod priority_tests {
             PriorityRule {
                 pattern: r"src/.*\.rs".to_string(),
                 score: 10,
+                ..Default::default()
             },
             PriorityRule {
                 pattern: r".*\.md".to_string(),
                 score: 5,
+                ..Default::default()
             },
         ];
         let priority = get_file_priority(path, &rules);
@@ -83,10 +359,12 @@ mod priority_tests {
             PriorityRule {
                 pattern: r"src/.*\.rs".to_string(),
                 score: 10,
+                ..Default::default()
             },
             PriorityRule {
                 pattern: r".*\.md".to_string(),
                 score: 5,
+                ..Default::default()
             },
         ];
         let priority = get_file_priority(path, &rules);
@@ -99,6 +377,7 @@ mod priority_tests {
         let rules = vec![PriorityRule {
             pattern: r"src/.*\.rs".to_string(),
             score: 10,
+            ..Default::default()
         }];
         let priority = get_file_priority(path, &rules);
         assert_eq!(priority, 10); // Should still match
@@ -106,6 +385,7 @@ mod priority_tests {
         let rules = vec![PriorityRule {
             pattern: r"src/[[.*\.rs".to_string(), // Invalid regex
             score: 10,
+            ..Default::default()
         }];
         let priority = get_file_priority(path, &rules);
         assert_eq!(priority, 0); // Invalid regex should not match
@@ -269,6 +549,7 @@ mod priority_tests {
         let rules = vec![PriorityRule {
             pattern: String::from(".*\\.rs$"),
             score: 100,
+            ..Default::default()
         }];
         assert_eq!(get_file_priority("src/main.rs", &rules), 100);
         assert_eq!(get_file_priority("README.md", &rules), 0);
@@ -280,10 +561,12 @@ mod priority_tests {
             PriorityRule {
                 pattern: String::from(".*\\.rs$"),
                 score: 100,
+                ..Default::default()
             },
             PriorityRule {
                 pattern: String::from("^src/.*"),
                 score: 50,
+                ..Default::default()
             },
         ];
         // File matches both patterns, should get sum of scores
@@ -299,6 +582,7 @@ mod priority_tests {
         let rules = vec![PriorityRule {
             pattern: String::from("[invalid regex"),
             score: 100,
+            ..Default::default()
         }];
         // Invalid regex should be skipped without affecting score
         assert_eq!(get_file_priority("any_file.txt", &rules), 0);
@@ -346,4 +630,374 @@ mod priority_tests {
         // Files with same timestamp should get same boost
         assert_eq!(boosts["file1.rs"], boosts["file2.rs"]);
     }
+
+    #[test]
+    fn test_get_file_priority_anchored_patterns() {
+        let rules = vec![PriorityRule {
+            pattern: String::from("^src/"),
+            score: 10,
+            ..Default::default()
+        }];
+        assert_eq!(get_file_priority("src/main.rs", &rules), 10);
+        // A plain substring match would wrongly accept these
+        assert_eq!(get_file_priority("tests/src/main.rs", &rules), 0);
+        assert_eq!(get_file_priority("lib/src/", &rules), 0);
+
+        let rules = vec![PriorityRule {
+            pattern: String::from(r"\.rs$"),
+            score: 20,
+            ..Default::default()
+        }];
+        assert_eq!(get_file_priority("src/main.rs", &rules), 20);
+        assert_eq!(get_file_priority("src/main.rs.bak", &rules), 0);
+        assert_eq!(get_file_priority("src/mainxrs", &rules), 0);
+    }
+
+    #[test]
+    fn test_priority_rule_matches() {
+        let rule = PriorityRule {
+            pattern: String::from(r"^docs/.*\.md$"),
+            score: 5,
+            ..Default::default()
+        };
+        assert!(rule.matches("docs/intro.md"));
+        assert!(!rule.matches("src/docs/intro.md"));
+
+        let invalid = PriorityRule {
+            pattern: String::from("(unclosed"),
+            score: 5,
+            ..Default::default()
+        };
+        // Repeated calls hit the compiled-pattern cache and still never match
+        assert!(!invalid.matches("(unclosed"));
+        assert!(!invalid.matches("(unclosed"));
+    }
+
+    // Write files, stage them and commit with a fixed timestamp using git2 only.
+    fn commit_files_at(repo: &git2::Repository, files: &[(&str, &str)], time: i64) {
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        let mut index = repo.index().unwrap();
+        for (name, content) in files {
+            fs::write(workdir.join(name), content).unwrap();
+            index.add_path(Path::new(name)).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = git2::Signature::new("Test User", "test@example.com", &git2::Time::new(time, 0))
+            .unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, "commit", &tree, &parents)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_get_recent_commit_times_git2_fixture() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+
+        commit_files_at(&repo, &[("a.txt", "a"), ("b.txt", "b")], 1_000_000);
+        commit_files_at(&repo, &[("b.txt", "b changed")], 2_000_000);
+
+        let times = get_recent_commit_times_git2(dir.path(), 100).unwrap();
+        assert_eq!(times.len(), 2);
+        // a.txt was only touched by the first commit
+        assert_eq!(times["a.txt"], 1_000_000);
+        assert_eq!(times["b.txt"], 2_000_000);
+    }
+
+    #[test]
+    fn test_show_commit_headers() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let head = |repo: &git2::Repository| {
+            repo.head()
+                .unwrap()
+                .peel_to_commit()
+                .unwrap()
+                .id()
+                .to_string()[..7]
+                .to_string()
+        };
+        commit_files_at(&repo, &[("a.txt", "a"), ("b.txt", "b")], 1_000_000);
+        let first = head(&repo);
+        commit_files_at(&repo, &[("b.txt", "b changed")], 2_000_000);
+        let second = head(&repo);
+        fs::write(dir.path().join("untracked.txt"), "new").unwrap();
+
+        let hashes = get_recent_commit_hashes_git2(dir.path(), 100).unwrap();
+        assert_eq!(
+            hashes,
+            HashMap::from([
+                ("a.txt".to_string(), first.clone()),
+                ("b.txt".to_string(), second.clone()),
+            ])
+        );
+
+        let config = YekConfig {
+            input_dirs: vec![dir.path().to_string_lossy().to_string()],
+            show_commit: true,
+            git_boost_max: Some(0),
+            ..Default::default()
+        };
+        let (output, _) = serialize_repo(&config).unwrap();
+        assert_eq!(
+            output,
+            format!(
+                ">>>> a.txt @{}\na\n>>>> b.txt @{}\nb changed\n>>>> untracked.txt\nnew",
+                first, second
+            )
+        );
+    }
+
+    #[test]
+    fn test_get_git_history_git2() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        commit_files_at(&repo, &[("a.txt", "a"), ("b.txt", "b")], 1_000_000);
+        commit_files_at(&repo, &[("b.txt", "b changed")], 2_000_000);
+        let head = repo
+            .head()
+            .unwrap()
+            .peel_to_commit()
+            .unwrap()
+            .id()
+            .to_string();
+
+        // Times, counts and hashes come from one walk
+        let history = get_git_history_git2(dir.path(), 100).unwrap();
+        assert_eq!(
+            history.times,
+            HashMap::from([
+                ("a.txt".to_string(), 1_000_000),
+                ("b.txt".to_string(), 2_000_000)
+            ])
+        );
+        assert_eq!(
+            history.counts,
+            HashMap::from([("a.txt".to_string(), 1), ("b.txt".to_string(), 2)])
+        );
+        assert_eq!(history.hashes["b.txt"], head[..7]);
+        assert!(get_git_history_git2(tempdir().unwrap().path(), 100).is_none());
+    }
+
+    #[test]
+    fn test_git_boost_max_outranks_priority_rule() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        commit_files_at(&repo, &[("old.txt", "old")], 1_000_000);
+        commit_files_at(&repo, &[("new.txt", "new")], 2_000_000);
+
+        let mut config = YekConfig::extend_config_with_defaults(
+            vec![dir.path().to_string_lossy().to_string()],
+            ".".to_string(),
+        );
+        config.priority_rules = vec![PriorityRule {
+            pattern: r"^old\.txt$".to_string(),
+            score: 20,
+            ..Default::default()
+        }];
+
+        // Highest priority sorts last
+        let order = |config: &YekConfig| -> Vec<String> {
+            let (_, files) = serialize_repo(config).unwrap();
+            files.into_iter().map(|f| f.rel_path).collect()
+        };
+
+        config.git_boost_max = Some(10);
+        assert_eq!(order(&config), vec!["new.txt", "old.txt"]);
+
+        config.git_boost_max = Some(500);
+        assert_eq!(order(&config), vec!["old.txt", "new.txt"]);
+    }
+
+    #[test]
+    fn test_git_frequency_weight_favors_frequently_changed_files() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        commit_files_at(&repo, &[("hot.txt", "v1")], 1_000_000);
+        commit_files_at(&repo, &[("hot.txt", "v2")], 1_100_000);
+        commit_files_at(&repo, &[("hot.txt", "v3")], 1_200_000);
+        commit_files_at(&repo, &[("new.txt", "new")], 2_000_000);
+
+        let counts = get_recent_commit_counts_git2(dir.path(), 100).unwrap();
+        assert_eq!(
+            counts,
+            HashMap::from([("hot.txt".to_string(), 3), ("new.txt".to_string(), 1)])
+        );
+        let times = get_recent_commit_times_git2(dir.path(), 100).unwrap();
+        assert_eq!(
+            compute_git_boost(&times, &counts, 100, 0.75),
+            HashMap::from([("hot.txt".to_string(), 75), ("new.txt".to_string(), 25)])
+        );
+
+        let mut config = YekConfig::extend_config_with_defaults(
+            vec![dir.path().to_string_lossy().to_string()],
+            ".".to_string(),
+        );
+        // Highest priority sorts last
+        let order = |config: &YekConfig| -> Vec<String> {
+            let (_, files) = serialize_repo(config).unwrap();
+            files.into_iter().map(|f| f.rel_path).collect()
+        };
+
+        assert_eq!(order(&config), vec!["hot.txt", "new.txt"]);
+
+        config.git_frequency_weight = 0.75;
+        assert_eq!(order(&config), vec!["new.txt", "hot.txt"]);
+    }
+
+    #[test]
+    fn test_priority_combine_adds_git_boost() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        commit_files_at(&repo, &[("src/old.rs", "old")], 1_000_000);
+        commit_files_at(&repo, &[("src/new.rs", "new")], 2_000_000);
+
+        let mut config = YekConfig::extend_config_with_defaults(
+            vec![dir.path().to_string_lossy().to_string()],
+            ".".to_string(),
+        );
+        config.priority_rules = vec![
+            PriorityRule {
+                pattern: r"^src/".to_string(),
+                score: 10,
+                ..Default::default()
+            },
+            PriorityRule {
+                pattern: r"\.rs$".to_string(),
+                score: 20,
+                ..Default::default()
+            },
+        ];
+
+        let priorities = |config: &YekConfig| -> Vec<(String, i32)> {
+            let (_, files) = serialize_repo(config).unwrap();
+            files
+                .into_iter()
+                .map(|f| (f.rel_path, f.priority))
+                .collect()
+        };
+
+        assert_eq!(
+            priorities(&config),
+            vec![
+                ("src/old.rs".to_string(), 30),
+                ("src/new.rs".to_string(), 130)
+            ]
+        );
+        config.priority_combine = "max".to_string();
+        assert_eq!(
+            priorities(&config),
+            vec![
+                ("src/old.rs".to_string(), 20),
+                ("src/new.rs".to_string(), 120)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_since_keeps_recently_committed_files() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        commit_files_at(&repo, &[("old.txt", "old")], 1_000_000);
+        commit_files_at(&repo, &[("new.txt", "new")], 2_000_000);
+        fs::write(dir.path().join("scratch.txt"), "untracked").unwrap();
+
+        let mut config = YekConfig::extend_config_with_defaults(
+            vec![dir.path().to_string_lossy().to_string()],
+            ".".to_string(),
+        );
+        config.since = Some("1500000".to_string());
+        let names = |config: &YekConfig| -> Vec<String> {
+            let (_, files) = serialize_repo(config).unwrap();
+            let mut names: Vec<String> = files.into_iter().map(|f| f.rel_path).collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names(&config), vec!["new.txt", "scratch.txt"]);
+
+        config.exclude_untracked = true;
+        assert_eq!(names(&config), vec!["new.txt"]);
+    }
+
+    #[test]
+    fn test_get_tracked_files_git2() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        commit_files_at(&repo, &[("a.txt", "a"), ("src/lib.rs", "lib")], 1_000_000);
+        fs::write(dir.path().join("untracked.txt"), "u").unwrap();
+
+        let tracked = get_tracked_files_git2(dir.path()).unwrap();
+        let mut tracked: Vec<&str> = tracked.iter().map(String::as_str).collect();
+        tracked.sort();
+        assert_eq!(tracked, vec!["a.txt", "src/lib.rs"]);
+    }
+
+    #[test]
+    fn test_depth_priority_favors_shallow_files() {
+        assert_eq!(depth_priority("README.md", 10), 0);
+        assert_eq!(depth_priority("a/b/c.rs", 10), -20);
+        assert_eq!(depth_priority("a/b/c.rs", -3), 6);
+
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("a/b/c/d/e");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(dir.path().join("README.md"), "root").unwrap();
+        fs::write(nested.join("README.md"), "nested").unwrap();
+
+        let mut config = YekConfig::extend_config_with_defaults(
+            vec![dir.path().to_string_lossy().to_string()],
+            ".".to_string(),
+        );
+        config.depth_priority = 10;
+        let (_, files) = serialize_repo(&config).unwrap();
+        let priority = |path: &str| files.iter().find(|f| f.rel_path == path).unwrap().priority;
+        assert_eq!(priority("README.md"), 0);
+        assert_eq!(priority("a/b/c/d/e/README.md"), -50);
+        // Most important last
+        assert_eq!(files.last().unwrap().rel_path, "README.md");
+    }
+
+    #[test]
+    fn test_serialize_repo_diff() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        commit_files_at(
+            &repo,
+            &[("a.txt", "a"), ("b.txt", "b"), ("src/lib.rs", "lib")],
+            1_000_000,
+        );
+        fs::write(dir.path().join("src/lib.rs"), "lib changed").unwrap();
+        fs::write(dir.path().join("new.txt"), "new").unwrap();
+
+        let mut config = YekConfig::extend_config_with_defaults(
+            vec![dir.path().to_string_lossy().to_string()],
+            ".".to_string(),
+        );
+        config.diff = Some("HEAD".to_string());
+        let (output, files) = serialize_repo(&config).unwrap();
+        assert_eq!(output, ">>>> src/lib.rs\nlib changed");
+        assert_eq!(files.len(), 1);
+
+        config.include_untracked = true;
+        let (_, files) = serialize_repo(&config).unwrap();
+        let mut names: Vec<&str> = files.iter().map(|f| f.rel_path.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["new.txt", "src/lib.rs"]);
+
+        // Paths are relative to the directory asked about, even below the repository root
+        let changed = get_changed_files_git2(&dir.path().join("src"), "HEAD", false).unwrap();
+        assert_eq!(changed.into_iter().collect::<Vec<_>>(), vec!["lib.rs"]);
+
+        config.diff = Some("no-such-ref".to_string());
+        assert!(serialize_repo(&config)
+            .unwrap_err()
+            .to_string()
+            .starts_with("diff: Unknown revision 'no-such-ref':"));
+    }
 }
@@ -0,0 +1,141 @@
+use std::path::Path;
+use yek::concat_files;
+use yek::config::YekConfig;
+use yek::parallel::ProcessedFile;
+use yek::strip::{collapse_blank_lines, strip_comments, truncate_long_lines};
+
+#[test]
+fn test_strip_comments_rust() {
+    let source = r##"//! Crate docs
+use std::fmt; // trailing comment
+
+/// Doc comment
+fn main() {
+    /* block
+       comment */
+    let url = "https://example.com // not a comment";
+    let raw = r#"raw "string" /* kept */"#;
+    let quote = '"'; // a char literal, not a string
+    let slash = '/';
+    println!("{} {} {} {}", url, raw, quote, slash); /* inline */ let x = 1;
+}
+
+fn longest<'a>(a: &'a str) -> &'a str { a } // lifetimes aren't chars
+"##;
+    let expected = r##"use std::fmt;
+
+fn main() {
+    let url = "https://example.com // not a comment";
+    let raw = r#"raw "string" /* kept */"#;
+    let quote = '"';
+    let slash = '/';
+    println!("{} {} {} {}", url, raw, quote, slash);  let x = 1;
+}
+
+fn longest<'a>(a: &'a str) -> &'a str { a }
+"##;
+    assert_eq!(
+        strip_comments(source, Path::new("src/main.rs")).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_strip_comments_python() {
+    let source = r##"#!/usr/bin/env python
+# Module comment
+import os  # trailing comment
+
+def main():
+    """Docstring with a # hash"""
+    path = "a # b"
+    other = 'c # d'
+    color = "#fff"
+    return f"{path}#{other}"  # done
+"##;
+    let expected = r##"#!/usr/bin/env python
+import os
+
+def main():
+    """Docstring with a # hash"""
+    path = "a # b"
+    other = 'c # d'
+    color = "#fff"
+    return f"{path}#{other}"
+"##;
+    assert_eq!(
+        strip_comments(source, Path::new("scripts/run.py")).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_strip_comments_unknown_language() {
+    assert_eq!(strip_comments("# Title\n", Path::new("README.md")), None);
+    assert_eq!(strip_comments("// x\n", Path::new("notes.txt")), None);
+}
+
+#[test]
+fn test_collapse_blank_lines() {
+    assert_eq!(
+        collapse_blank_lines("a\n\n\n  \nb\n\nc\n\n\n"),
+        "a\n\nb\n\nc\n\n"
+    );
+    assert_eq!(collapse_blank_lines("a\nb"), "a\nb");
+}
+
+#[test]
+fn test_concat_files_strip_comments_and_collapse_blank_lines() {
+    let files = vec![ProcessedFile {
+        priority: 0,
+        file_index: 0,
+        rel_path: "src/lib.rs".to_string(),
+        content: "// header\n\nfn a() {}\n\n\n\n// gap\nfn b() {}\n".to_string(),
+        commit: None,
+    }];
+    let mut config = YekConfig {
+        strip_comments: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        concat_files(&files, &config).unwrap(),
+        ">>>> src/lib.rs\n\nfn a() {}\n\n\n\nfn b() {}\n"
+    );
+
+    config.collapse_blank_lines = true;
+    assert_eq!(
+        concat_files(&files, &config).unwrap(),
+        ">>>> src/lib.rs\n\nfn a() {}\n\nfn b() {}\n"
+    );
+}
+
+#[test]
+fn test_truncate_long_lines() {
+    assert_eq!(
+        truncate_long_lines("short\nabcdefgh\r\néèéèé\n", 4).unwrap(),
+        "shor…[truncated 1 chars]\nabcd…[truncated 4 chars]\r\néèéè…[truncated 1 chars]\n"
+    );
+    assert_eq!(truncate_long_lines("abcd\nab\n", 4), None);
+}
+
+#[test]
+fn test_concat_files_max_line_length() {
+    let files = vec![ProcessedFile {
+        priority: 0,
+        file_index: 0,
+        rel_path: "app.min.js".to_string(),
+        content: format!("// minified\n{}\n", "x".repeat(100_000)),
+        commit: None,
+    }];
+    let config = YekConfig {
+        max_line_length: Some(200),
+        ..Default::default()
+    };
+    assert_eq!(
+        concat_files(&files, &config).unwrap(),
+        format!(
+            ">>>> app.min.js\n// minified\n{}…[truncated 99800 chars]\n",
+            "x".repeat(200)
+        )
+    );
+}
@@ -43,6 +43,41 @@ mod symlink_tests {
         );
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks() {
+        let temp_dir = tempdir().expect("failed to create temp dir");
+        let base_path = temp_dir.path().join("repo");
+        let outside = temp_dir.path().join("shared");
+        fs::create_dir(&base_path).unwrap();
+        fs::create_dir(&outside).unwrap();
+        fs::write(outside.join("lib.rs"), "shared code").unwrap();
+        std::os::unix::fs::symlink(&outside, base_path.join("shared")).unwrap();
+        // A self-referential symlink must not make the walk hang
+        std::os::unix::fs::symlink(&base_path, base_path.join("loop")).unwrap();
+        fs::write(base_path.join("main.rs"), "main code").unwrap();
+
+        let mut config = YekConfig::extend_config_with_defaults(
+            vec![base_path.to_string_lossy().to_string()],
+            ".".to_string(),
+        );
+        let boost_map = HashMap::new();
+        let names = |config: &YekConfig| {
+            let mut names: Vec<String> = process_files_parallel(&base_path, config, &boost_map)
+                .expect("processing failed")
+                .into_iter()
+                .map(|pf| pf.rel_path)
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names(&config), vec!["main.rs"]);
+
+        config.follow_symlinks = true;
+        assert_eq!(names(&config), vec!["main.rs", "shared/lib.rs"]);
+    }
+
     // For non-unix systems, we skip the symlink test.
     #[cfg(not(unix))]
     #[test]